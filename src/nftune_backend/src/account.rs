@@ -0,0 +1,473 @@
+use std::collections::BTreeMap;
+use candid::Principal;
+use ic_cdk::api::time;
+use crate::types::{ActivityKind, OutboundEmail, ProjectDisposition, Role, User, UserDataExport};
+use crate::storage::{
+    get_user_by_principal, remove_user, remove_handle, remove_role, get_all_roles,
+    get_linked_principals_for_account, remove_linked_principal, get_link_challenges_for_account,
+    remove_link_challenge, get_delegations_for_account, remove_delegation, remove_suspension,
+    get_projects_by_owner, save_project, get_tracks_by_project, get_nfts_by_creator, remove_nft,
+    get_collaborations_by_user, get_collaborations_by_project, remove_collaboration, get_project_by_id,
+    get_email_verification, remove_email_verification, get_following_page, get_followers_page,
+    remove_follow, get_ownership_transfers_for_party, remove_ownership_transfer, get_invitations_by_invitee,
+    remove_invitation, get_track_versions_by_uploader, get_track_by_id, get_stems_by_contributor,
+    get_outbound_emails_to, remove_outbound_email,
+};
+use crate::social::adjust_counts;
+use crate::history::save_project_with_history;
+use crate::activity;
+use crate::auth::{require_authenticated, require_active, get_role, resolve_principal};
+
+/// Collects every record owned by or describing the caller.
+/// Suspended users may still export their data.
+pub fn export_my_data() -> Result<UserDataExport, String> {
+    export_account(require_authenticated()?, time())
+}
+
+fn export_account(account: Principal, now: u64) -> Result<UserDataExport, String> {
+    let user = get_user_by_principal(account)
+        .ok_or_else(|| "User not found".to_string())?;
+
+    let projects = get_projects_by_owner(account);
+
+    let track_versions = get_track_versions_by_uploader(account);
+
+    // Tracks in the user's projects plus tracks they uploaded to elsewhere, once each
+    let mut tracks = BTreeMap::new();
+    for track in projects.iter().flat_map(|project| get_tracks_by_project(&project.id)) {
+        tracks.insert(track.id.clone(), track);
+    }
+    for version in &track_versions {
+        if !tracks.contains_key(&version.track_id) {
+            if let Some(track) = get_track_by_id(&version.track_id) {
+                tracks.insert(track.id.clone(), track);
+            }
+        }
+    }
+
+    Ok(UserDataExport {
+        role: get_role(account),
+        linked_principals: get_linked_principals_for_account(account),
+        delegations: get_delegations_for_account(account),
+        projects,
+        tracks: tracks.into_values().collect(),
+        track_versions,
+        stems: get_stems_by_contributor(account),
+        nfts: get_nfts_by_creator(account),
+        collaborations: get_collaborations_by_user(account),
+        invitations: get_invitations_by_invitee(account),
+        ownership_transfers: get_ownership_transfers_for_party(account),
+        following: collect_edges(|after| get_following_page(account, after, 100)),
+        followers: collect_edges(|after| get_followers_page(account, after, 100)),
+        emails: emails_for(&user),
+        user,
+        exported_at: now,
+    })
+}
+
+/// Walks every page of one side of the follow graph.
+fn collect_edges(page: impl Fn(Option<Principal>) -> Vec<(Principal, u64)>) -> Vec<Principal> {
+    let mut principals = Vec::new();
+    loop {
+        let edges = page(principals.last().copied());
+        if edges.is_empty() {
+            return principals;
+        }
+        principals.extend(edges.into_iter().map(|(principal, _)| principal));
+    }
+}
+
+/// Outbox mail addressed to the user's email, or to the address they are
+/// verifying.
+fn emails_for(user: &User) -> Vec<OutboundEmail> {
+    let mut emails = get_outbound_emails_to(&user.email);
+
+    if let Some(verification) = get_email_verification(user.principal) {
+        if !verification.email.eq_ignore_ascii_case(&user.email) {
+            emails.extend(get_outbound_emails_to(&verification.email));
+            emails.sort_by_key(|email| email.id);
+        }
+    }
+
+    emails
+}
+
+/// Permanently removes the caller's account.
+///
+/// Owned projects are archived or transferred according to `disposition`,
+/// collaborator entries, unminted NFTs and undelivered mail are removed, and
+/// minted NFTs are kept unchanged because they mirror on-chain tokens.
+pub fn delete_account(disposition: ProjectDisposition) -> Result<(), String> {
+    remove_account(require_active()?, disposition, time())
+}
+
+fn remove_account(account: Principal, disposition: ProjectDisposition, now: u64) -> Result<(), String> {
+    let user = get_user_by_principal(account)
+        .ok_or_else(|| "User not found".to_string())?;
+
+    if get_role(account) == Role::Admin {
+        let admins = get_all_roles()
+            .into_iter()
+            .filter(|(_, role)| *role == Role::Admin)
+            .count();
+
+        if admins <= 1 {
+            return Err("The last admin cannot delete their account".to_string());
+        }
+    }
+
+    if let ProjectDisposition::Transfer(new_owner) = &disposition {
+        let new_owner = resolve_principal(*new_owner);
+        if new_owner == account || get_user_by_principal(new_owner).is_none() {
+            return Err("Projects must be transferred to another registered user".to_string());
+        }
+    }
+
+    for mut project in get_projects_by_owner(account) {
+        let kind = match &disposition {
+            ProjectDisposition::Archive => {
+                if project.archived_at.is_some() {
+                    continue;
+                }
+                project.archived_at = Some(now);
+                ActivityKind::ProjectArchived
+            }
+            ProjectDisposition::Transfer(new_owner) => {
+                let new_owner = resolve_principal(*new_owner);
+
+                // The new owner no longer needs a collaborator entry
+                project.collaborators.retain(|c| c != &new_owner.to_string());
+                for collaboration in get_collaborations_by_project(&project.id) {
+                    if collaboration.user_principal == new_owner {
+                        remove_collaboration(&collaboration.id);
+                    }
+                }
+
+                project.owner = new_owner;
+                ActivityKind::OwnershipTransferred { from: account, to: new_owner }
+            }
+        };
+        project.updated_at = now;
+        let project_id = project.id.clone();
+        save_project_with_history(project, account);
+        activity::record(&project_id, account, kind);
+    }
+
+    for transfer in get_ownership_transfers_for_party(account) {
+        remove_ownership_transfer(&transfer.project_id);
+    }
+
+    for collaboration in get_collaborations_by_user(account) {
+        if let Some(mut project) = get_project_by_id(&collaboration.project_id) {
+            project.collaborators.retain(|c| c != &account.to_string());
+            project.updated_at = now;
+            save_project_with_history(project, account);
+        }
+        remove_collaboration(&collaboration.id);
+    }
+
+    for nft in get_nfts_by_creator(account)
+        .into_iter()
+        .filter(|nft| !nft.is_minted)
+    {
+        if let Some(mut project) = get_project_by_id(&nft.project_id) {
+            project.nfts.retain(|id| id != &nft.id);
+            save_project(project);
+        }
+        remove_nft(&nft.id);
+    }
+
+    for invitation in get_invitations_by_invitee(account) {
+        remove_invitation(&invitation.id);
+    }
+
+    for link in get_linked_principals_for_account(account) {
+        remove_linked_principal(link.principal);
+    }
+
+    for challenge in get_link_challenges_for_account(account) {
+        remove_link_challenge(&challenge.code);
+    }
+
+    for delegation in get_delegations_for_account(account) {
+        remove_delegation(delegation.delegate);
+    }
+
+    // Drain both sides of the follow graph, keeping counterparties' counts in sync
+    loop {
+        let following = get_following_page(account, None, 100);
+        if following.is_empty() {
+            break;
+        }
+        for (followee, _) in following {
+            remove_follow(account, followee);
+            adjust_counts(account, followee);
+        }
+    }
+
+    loop {
+        let followers = get_followers_page(account, None, 100);
+        if followers.is_empty() {
+            break;
+        }
+        for (follower, _) in followers {
+            remove_follow(follower, account);
+            adjust_counts(follower, account);
+        }
+    }
+
+    if let Some(handle) = &user.handle {
+        remove_handle(handle);
+    }
+
+    // Any remaining suspension has expired, otherwise require_active would have failed
+    for email in emails_for(&user) {
+        remove_outbound_email(email.id);
+    }
+
+    remove_suspension(account);
+    remove_email_verification(account);
+    remove_role(account);
+    remove_user(account);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        Delegation, EmailStatus, LinkChallenge, LinkedPrincipal, OwnershipTransfer, PrivacySettings, Scope,
+        Stem, StemRole, TrackVersion,
+    };
+    use crate::storage::{
+        save_user, push_track_version, save_stem, save_ownership_transfer, push_outbound_email,
+        remove_stem, rebuild_account_indexes, save_handle, get_principal_by_handle, save_role,
+        save_linked_principal, get_linked_principal, save_link_challenge, get_link_challenge,
+        save_delegation, get_delegation, save_follow, is_following, get_ownership_transfer_by_project,
+        get_outbound_emails_to,
+    };
+
+    fn alice() -> Principal {
+        Principal::from_slice(&[1])
+    }
+
+    fn bob() -> Principal {
+        Principal::from_slice(&[2])
+    }
+
+    fn carol() -> Principal {
+        Principal::from_slice(&[3])
+    }
+
+    fn register(principal: Principal, email: &str) {
+        save_user(User {
+            principal,
+            handle: None,
+            handle_updated_at: None,
+            name: None,
+            email: email.to_string(),
+            email_status: EmailStatus::Verified,
+            email_verified_at: Some(0),
+            avatar_url: None,
+            bio: None,
+            links: vec![],
+            location: None,
+            privacy: PrivacySettings::default(),
+            follower_count: 0,
+            following_count: 0,
+            created_at: 0,
+            updated_at: 0,
+        });
+    }
+
+    fn upload(track_id: &str, uploaded_by: Principal) {
+        push_track_version(TrackVersion {
+            track_id: track_id.to_string(),
+            number: 0,
+            ipfs_hash: "QmHash".to_string(),
+            duration: 180,
+            uploaded_by,
+            notes: None,
+            created_at: 0,
+        });
+    }
+
+    fn stem(id: &str, contributor: Principal) -> Stem {
+        Stem {
+            id: id.to_string(),
+            track_id: "track".to_string(),
+            role: StemRole::Drums,
+            ipfs_hash: "QmStem".to_string(),
+            contributor,
+            sample_rate: 48_000,
+            channels: 2,
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    fn offer(project_id: &str, from: Principal, to: Principal) {
+        save_ownership_transfer(OwnershipTransfer {
+            project_id: project_id.to_string(),
+            from,
+            to,
+            requires_collaborator_approval: false,
+            approvals: vec![],
+            proposed_at: 0,
+            expires_at: u64::MAX,
+        });
+    }
+
+    fn mail(to: &str) {
+        push_outbound_email(OutboundEmail {
+            id: 0,
+            to: to.to_string(),
+            subject: "Hello".to_string(),
+            body: "Hello".to_string(),
+            created_at: 0,
+        });
+    }
+
+    #[test]
+    fn the_export_holds_only_the_accounts_uploads_transfers_and_mail() {
+        register(alice(), "alice@example.com");
+        register(bob(), "bob@example.com");
+
+        upload("track", alice());
+        upload("track", bob());
+        upload("other", alice());
+        save_stem(stem("a", alice()));
+        save_stem(stem("b", bob()));
+        offer("received", bob(), alice());
+        offer("offered", alice(), carol());
+        offer("unrelated", bob(), carol());
+        mail("ALICE@example.com");
+        mail("bob@example.com");
+
+        let export = export_account(alice(), 7).unwrap();
+
+        let versions: Vec<_> = export.track_versions.iter().map(|v| (v.track_id.as_str(), v.number)).collect();
+        assert_eq!(versions, [("other", 1), ("track", 1)]);
+        assert_eq!(export.stems.len(), 1);
+        assert_eq!(export.stems[0].id, "a");
+
+        let mut transfers: Vec<_> = export.ownership_transfers.iter().map(|t| t.project_id.as_str()).collect();
+        transfers.sort();
+        assert_eq!(transfers, ["offered", "received"]);
+
+        assert_eq!(export.emails.len(), 1);
+        assert_eq!(export.emails[0].to, "ALICE@example.com");
+        assert_eq!(export.exported_at, 7);
+    }
+
+    #[test]
+    fn indexes_follow_replaced_and_removed_records() {
+        register(alice(), "alice@example.com");
+
+        // A new offer for the same project replaces the earlier recipient
+        offer("project", bob(), alice());
+        offer("project", bob(), carol());
+        save_stem(stem("a", alice()));
+        save_stem(stem("a", bob()));
+        save_stem(stem("b", alice()));
+        remove_stem("track", "b");
+
+        let export = export_account(alice(), 0).unwrap();
+        assert!(export.ownership_transfers.is_empty());
+        assert!(export.stems.is_empty());
+    }
+
+    #[test]
+    fn rebuilt_indexes_find_the_same_records() {
+        register(alice(), "alice@example.com");
+        upload("track", alice());
+        save_stem(stem("a", alice()));
+        offer("project", bob(), alice());
+        mail("alice@example.com");
+
+        rebuild_account_indexes();
+
+        let export = export_account(alice(), 0).unwrap();
+        assert_eq!(export.track_versions.len(), 1);
+        assert_eq!(export.stems.len(), 1);
+        assert_eq!(export.ownership_transfers.len(), 1);
+        assert_eq!(export.emails.len(), 1);
+    }
+
+    #[test]
+    fn deleting_an_account_removes_its_records_and_follow_edges() {
+        register(alice(), "alice@example.com");
+        register(bob(), "bob@example.com");
+        save_handle("alice", alice());
+        save_user(User { handle: Some("alice".to_string()), ..get_user_by_principal(alice()).unwrap() });
+        save_role(alice(), Role::Moderator);
+        save_linked_principal(LinkedPrincipal { principal: carol(), account: alice(), linked_at: 0 });
+        save_link_challenge(LinkChallenge { code: "ABCD2345".to_string(), account: alice(), created_at: 0, expires_at: 1 });
+        save_delegation(Delegation {
+            delegate: Principal::from_slice(&[9]),
+            account: alice(),
+            scopes: vec![Scope::TracksWrite],
+            created_at: 0,
+            expires_at: 1,
+            revoked_at: None,
+            last_used_at: None,
+        });
+
+        // Bob follows Alice and Alice follows Bob
+        save_follow(bob(), alice(), 0);
+        save_follow(alice(), bob(), 0);
+        save_user(User { follower_count: 1, following_count: 1, ..get_user_by_principal(bob()).unwrap() });
+
+        offer("received", bob(), alice());
+        offer("unrelated", bob(), carol());
+        mail("alice@example.com");
+        mail("bob@example.com");
+
+        remove_account(alice(), ProjectDisposition::Archive, 0).unwrap();
+
+        assert!(get_user_by_principal(alice()).is_none());
+        assert_eq!(get_principal_by_handle("alice"), None);
+        assert_eq!(get_role(alice()), Role::User);
+        assert!(get_linked_principal(carol()).is_none());
+        assert!(get_link_challenge("ABCD2345").is_none());
+        assert!(get_delegation(Principal::from_slice(&[9])).is_none());
+        assert!(get_ownership_transfer_by_project("received").is_none());
+        assert!(get_ownership_transfer_by_project("unrelated").is_some());
+        assert!(get_outbound_emails_to("alice@example.com").is_empty());
+        assert_eq!(get_outbound_emails_to("bob@example.com").len(), 1);
+
+        assert!(!is_following(bob(), alice()));
+        assert!(!is_following(alice(), bob()));
+        let counterparty = get_user_by_principal(bob()).unwrap();
+        assert_eq!((counterparty.follower_count, counterparty.following_count), (0, 0));
+    }
+
+    #[test]
+    fn the_last_admin_cannot_delete_their_account() {
+        register(alice(), "alice@example.com");
+        save_role(alice(), Role::Admin);
+
+        let result = remove_account(alice(), ProjectDisposition::Archive, 0);
+        assert_eq!(result.unwrap_err(), "The last admin cannot delete their account");
+
+        save_role(bob(), Role::Admin);
+        assert!(remove_account(alice(), ProjectDisposition::Archive, 0).is_ok());
+    }
+
+    #[test]
+    fn projects_can_only_be_transferred_to_another_registered_user() {
+        register(alice(), "alice@example.com");
+        register(bob(), "bob@example.com");
+        let error = "Projects must be transferred to another registered user";
+
+        let result = remove_account(alice(), ProjectDisposition::Transfer(carol()), 0);
+        assert_eq!(result.unwrap_err(), error);
+
+        let result = remove_account(alice(), ProjectDisposition::Transfer(alice()), 0);
+        assert_eq!(result.unwrap_err(), error);
+        assert!(get_user_by_principal(alice()).is_some());
+
+        assert!(remove_account(alice(), ProjectDisposition::Transfer(bob()), 0).is_ok());
+    }
+}
@@ -0,0 +1,82 @@
+use candid::Principal;
+use ic_cdk::api::time;
+use crate::types::{ActivityEvent, ActivityKind, ActivityPage, PageRequest};
+use crate::storage::{
+    push_activity_event, scan_project_activity, get_project_activity_before, get_projects_by_owner,
+    get_collaborations_by_user,
+};
+use crate::auth::{require_authenticated, caller_principal};
+use crate::pagination::{encode_cursor, resolve_page};
+use crate::projects::get_visible_project;
+
+/// Appends an event to a project's activity log.
+pub fn record(project_id: &str, actor: Principal, kind: ActivityKind) {
+    push_activity_event(ActivityEvent {
+        id: 0,
+        project_id: project_id.to_string(),
+        actor,
+        kind,
+        created_at: time(),
+    });
+}
+
+/// A project's activity, newest first.
+pub fn get_project_activity(project_id: String, page: PageRequest) -> Result<ActivityPage, String> {
+    if get_visible_project(&project_id, caller_principal()).is_none() {
+        return Err("Project not found".to_string());
+    }
+
+    let (after, limit) = resolve_page(&page)?;
+    let page = scan_project_activity(&project_id, after, limit);
+
+    Ok(ActivityPage {
+        items: page.items,
+        next_cursor: page.last_key.map(|key| encode_cursor(&key)),
+    })
+}
+
+/// Activity across every project the caller owns or collaborates on,
+/// newest first.
+pub fn get_activity_feed(page: PageRequest) -> Result<ActivityPage, String> {
+    let caller = require_authenticated()?;
+    let (after, limit) = resolve_page(&page)?;
+
+    // The cursor is the id of the last event on the previous page
+    let before_id = after
+        .map(|cursor| cursor.parse::<u64>())
+        .transpose()
+        .map_err(|_| "Invalid cursor".to_string())?;
+
+    let mut project_ids: Vec<String> = get_projects_by_owner(caller)
+        .into_iter()
+        .map(|project| project.id)
+        .collect();
+
+    for collaboration in get_collaborations_by_user(caller) {
+        if !project_ids.contains(&collaboration.project_id) {
+            project_ids.push(collaboration.project_id);
+        }
+    }
+
+    // Take a page from each log, then keep the newest across all of them
+    let mut events = Vec::new();
+    let mut has_more = false;
+
+    for project_id in project_ids {
+        let project_events = get_project_activity_before(&project_id, before_id, limit);
+        has_more |= project_events.len() == limit;
+        events.extend(project_events);
+    }
+
+    events.sort_by_key(|event| std::cmp::Reverse(event.id));
+    has_more |= events.len() > limit;
+    events.truncate(limit);
+
+    Ok(ActivityPage {
+        next_cursor: events
+            .last()
+            .filter(|_| has_more)
+            .map(|event| encode_cursor(&event.id.to_string())),
+        items: events,
+    })
+}
@@ -0,0 +1,189 @@
+use candid::Principal;
+use ic_cdk::api::time;
+use crate::types::{Role, RoleAssignment, Suspension, SuspensionKind};
+use crate::storage::{
+    save_role, remove_role, get_all_roles, get_suspension_by_principal, save_suspension,
+    remove_suspension, get_all_suspensions,
+};
+use crate::auth::{require_admin, require_moderator, get_role, resolve_principal};
+use crate::validation;
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+/// Grants the admin role to the principals passed at install or upgrade time.
+/// Existing assignments are left untouched, so upgrades can only add admins.
+pub fn seed_admins(admins: Vec<Principal>) {
+    for admin in admins {
+        if admin != Principal::anonymous() {
+            save_role(admin, Role::Admin);
+        }
+    }
+}
+
+pub fn set_user_role(principal: Principal, role: Role) -> Result<RoleAssignment, String> {
+    require_admin()?;
+    let principal = resolve_principal(principal);
+
+    if principal == Principal::anonymous() {
+        return Err("Cannot assign a role to the anonymous principal".to_string());
+    }
+
+    // Never leave the canister without an administrator
+    if get_role(principal) == Role::Admin && role != Role::Admin && count_admins() <= 1 {
+        return Err("Cannot remove the last admin".to_string());
+    }
+
+    match role {
+        Role::User => remove_role(principal),
+        _ => save_role(principal, role),
+    }
+
+    Ok(RoleAssignment { principal, role })
+}
+
+pub fn get_user_role(principal: Principal) -> Role {
+    get_role(resolve_principal(principal))
+}
+
+pub fn list_role_assignments() -> Result<Vec<RoleAssignment>, String> {
+    require_moderator()?;
+
+    Ok(get_all_roles()
+        .into_iter()
+        .map(|(principal, role)| RoleAssignment { principal, role })
+        .collect())
+}
+
+/// Temporarily blocks a principal from changing any state.
+pub fn suspend_user(principal: Principal, reason: String, duration_seconds: u64) -> Result<Suspension, String> {
+    let moderator = require_moderator()?;
+
+    if duration_seconds == 0 {
+        return Err("Suspension duration must be greater than zero".to_string());
+    }
+
+    if let Some(existing) = get_suspension_by_principal(resolve_principal(principal)) {
+        if existing.kind == SuspensionKind::Banned {
+            return Err("User is already banned".to_string());
+        }
+    }
+
+    let expires_at = time().saturating_add(duration_seconds.saturating_mul(NANOS_PER_SECOND));
+    issue_suspension(moderator, principal, SuspensionKind::Suspended, reason, Some(expires_at))
+}
+
+/// Permanently blocks a principal until an admin lifts the ban.
+pub fn ban_user(principal: Principal, reason: String) -> Result<Suspension, String> {
+    let admin = require_admin()?;
+    issue_suspension(admin, principal, SuspensionKind::Banned, reason, None)
+}
+
+pub fn unsuspend_user(principal: Principal) -> Result<(), String> {
+    let moderator = require_moderator()?;
+    let principal = resolve_principal(principal);
+
+    let suspension = get_suspension_by_principal(principal)
+        .ok_or_else(|| "User is not suspended".to_string())?;
+
+    if suspension.kind == SuspensionKind::Banned && get_role(moderator) != Role::Admin {
+        return Err("Only admins can lift bans".to_string());
+    }
+
+    remove_suspension(principal);
+    Ok(())
+}
+
+pub fn get_suspension(principal: Principal) -> Result<Option<Suspension>, String> {
+    require_moderator()?;
+    Ok(get_suspension_by_principal(resolve_principal(principal)))
+}
+
+pub fn list_suspensions() -> Result<Vec<Suspension>, String> {
+    require_moderator()?;
+    Ok(get_all_suspensions())
+}
+
+/// Removes suspensions whose expiry has passed. Runs on a timer.
+pub fn lift_expired_suspensions() {
+    let now = time();
+
+    for suspension in get_all_suspensions() {
+        if suspension.expires_at.is_some_and(|expires_at| expires_at <= now) {
+            remove_suspension(suspension.principal);
+        }
+    }
+}
+
+fn issue_suspension(
+    issued_by: Principal,
+    principal: Principal,
+    kind: SuspensionKind,
+    reason: String,
+    expires_at: Option<u64>,
+) -> Result<Suspension, String> {
+    // Suspensions apply to the whole account, not a single linked device
+    let principal = resolve_principal(principal);
+
+    if principal == issued_by {
+        return Err("Cannot suspend yourself".to_string());
+    }
+
+    if get_role(principal) >= get_role(issued_by) {
+        return Err("Cannot suspend a user with an equal or higher role".to_string());
+    }
+
+    validation::validate_suspension_reason(&reason)?;
+
+    let suspension = Suspension {
+        principal,
+        kind,
+        reason: reason.trim().to_string(),
+        issued_by,
+        issued_at: time(),
+        expires_at,
+    };
+
+    save_suspension(suspension.clone());
+    Ok(suspension)
+}
+
+fn count_admins() -> usize {
+    get_all_roles()
+        .into_iter()
+        .filter(|(_, role)| *role == Role::Admin)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::{BoundedStorable, Storable};
+    use super::*;
+
+    #[test]
+    fn reasons_must_be_short_non_empty_text() {
+        assert!(validation::validate_suspension_reason(" Spam ").is_ok());
+
+        assert!(validation::validate_suspension_reason(" ").is_err());
+        assert!(validation::validate_suspension_reason(&"a".repeat(141)).is_err());
+        assert!(validation::validate_suspension_reason("spam\u{0}").is_err());
+    }
+
+    #[test]
+    fn a_suspension_with_the_longest_reason_fits_its_bound() {
+        // Emoji take four bytes, the most a character can
+        let reason = "🎸".repeat(140);
+        validation::validate_suspension_reason(&reason).unwrap();
+
+        let suspension = Suspension {
+            principal: Principal::from_slice(&[255; 29]),
+            kind: SuspensionKind::Suspended,
+            reason,
+            issued_by: Principal::from_slice(&[255; 29]),
+            issued_at: u64::MAX,
+            expires_at: Some(u64::MAX),
+        };
+
+        let size = suspension.to_bytes().len();
+        assert!(size <= Suspension::MAX_SIZE as usize, "{} bytes", size);
+    }
+}
@@ -1,11 +1,22 @@
 use candid::Principal;
 use ic_cdk::{caller, api::time};
-use crate::types::User;
-use crate::storage::{get_user_by_principal, save_user};
+use crate::types::{Delegation, EmailStatus, PrivacySettings, PublicProfile, Role, Scope, SuspensionKind, User};
+use crate::storage::{get_user_by_principal, save_user, get_principal_by_handle, save_handle, remove_handle, get_role_by_principal, get_suspension_by_principal, get_linked_principal, get_delegation, save_delegation};
+use crate::validation;
+use crate::email;
 
 pub fn register_user(name: Option<String>, email: String) -> Result<User, String> {
-    let principal = caller();
-    
+    let now = time();
+    let mut user = new_user(caller(), name, email, now)?;
+
+    email::issue_verification_code(&mut user, email::mailer(), now)?;
+
+    save_user(user.clone());
+    Ok(user)
+}
+
+/// Checks that `principal` may register and builds its unsaved account.
+fn new_user(principal: Principal, name: Option<String>, email: String, now: u64) -> Result<User, String> {
     if principal == Principal::anonymous() {
         return Err("Anonymous users cannot register".to_string());
     }
@@ -15,20 +26,41 @@ pub fn register_user(name: Option<String>, email: String) -> Result<User, String
         return Err("User already registered".to_string());
     }
 
-    let user = User {
+    if get_linked_principal(principal).is_some() {
+        return Err("Principal is linked to an existing account".to_string());
+    }
+
+    if get_delegation(principal).is_some() {
+        return Err("Principal is registered as a delegate".to_string());
+    }
+
+    if let Some(name) = &name {
+        validation::validate_name(name)?;
+    }
+    validation::validate_email(&email)?;
+
+    Ok(User {
         principal,
+        handle: None,
+        handle_updated_at: None,
         name,
         email,
+        email_status: EmailStatus::Unverified,
+        email_verified_at: None,
         avatar_url: None,
-        created_at: time(),
-    };
-
-    save_user(user.clone());
-    Ok(user)
+        bio: None,
+        links: vec![],
+        location: None,
+        privacy: PrivacySettings::default(),
+        follower_count: 0,
+        following_count: 0,
+        created_at: now,
+        updated_at: now,
+    })
 }
 
 pub fn get_user() -> Result<User, String> {
-    let principal = caller();
+    let principal = resolve_principal(caller());
     
     if principal == Principal::anonymous() {
         return Err("Anonymous users cannot access user data".to_string());
@@ -38,8 +70,184 @@ pub fn get_user() -> Result<User, String> {
         .ok_or_else(|| "User not found".to_string())
 }
 
+/// Updates the caller's profile. `None` leaves a field unchanged; an empty
+/// string clears an optional field and an empty list clears the links.
+pub fn update_user(
+    name: Option<String>,
+    email: Option<String>,
+    avatar_url: Option<String>,
+    bio: Option<String>,
+    links: Option<Vec<String>>,
+    location: Option<String>,
+) -> Result<User, String> {
+    let principal = require_active()?;
+
+    let mut user = get_user_by_principal(principal)
+        .ok_or_else(|| "User not found".to_string())?;
+
+    if let Some(new_name) = name {
+        if new_name.is_empty() {
+            user.name = None;
+        } else {
+            validation::validate_name(&new_name)?;
+            user.name = Some(new_name.trim().to_string());
+        }
+    }
+
+    if let Some(new_email) = email {
+        validation::validate_email(&new_email)?;
+
+        // A new address has to be verified again
+        if new_email != user.email {
+            user.email = new_email;
+            user.email_status = EmailStatus::Unverified;
+            user.email_verified_at = None;
+            email::issue_verification_code(&mut user, email::mailer(), time())?;
+        }
+    }
+
+    if let Some(new_avatar_url) = avatar_url {
+        if new_avatar_url.is_empty() {
+            user.avatar_url = None;
+        } else {
+            validation::validate_avatar_url(&new_avatar_url)?;
+            user.avatar_url = Some(new_avatar_url);
+        }
+    }
+
+    if let Some(new_bio) = bio {
+        validation::validate_bio(&new_bio)?;
+        user.bio = if new_bio.is_empty() { None } else { Some(new_bio) };
+    }
+
+    if let Some(new_links) = links {
+        validation::validate_links(&new_links)?;
+        user.links = new_links;
+    }
+
+    if let Some(new_location) = location {
+        validation::validate_location(&new_location)?;
+        user.location = if new_location.is_empty() { None } else { Some(new_location) };
+    }
+
+    user.updated_at = time();
+    save_user(user.clone());
+    Ok(user)
+}
+
+/// Minimum time between handle changes, so released handles can't be
+/// cycled through quickly to impersonate other artists.
+const HANDLE_CHANGE_COOLDOWN_NS: u64 = 30 * 24 * 60 * 60 * 1_000_000_000;
+
+/// Claims or renames the caller's handle. Handles are unique ignoring case;
+/// the previous handle is released as soon as the new one is taken.
+pub fn set_handle(handle: String) -> Result<User, String> {
+    let principal = require_active()?;
+
+    let mut user = get_user_by_principal(principal)
+        .ok_or_else(|| "User not found".to_string())?;
+
+    validation::validate_handle(&handle)?;
+
+    if let Some(current) = &user.handle {
+        if current == &handle {
+            return Ok(user);
+        }
+
+        // Changing only the capitalisation keeps the same index entry
+        let case_change = current.eq_ignore_ascii_case(&handle);
+        if let Some(last_change) = user.handle_updated_at {
+            if !case_change && time() < last_change + HANDLE_CHANGE_COOLDOWN_NS {
+                return Err("Handle can only be changed once every 30 days".to_string());
+            }
+        }
+    }
+
+    if let Some(owner) = get_principal_by_handle(&handle) {
+        if owner != principal {
+            return Err("Handle is already taken".to_string());
+        }
+    }
+
+    if let Some(previous) = user.handle.take() {
+        remove_handle(&previous);
+    }
+
+    save_handle(&handle, principal);
+    user.handle = Some(handle);
+    user.handle_updated_at = Some(time());
+    user.updated_at = time();
+
+    save_user(user.clone());
+    Ok(user)
+}
+
+pub fn get_user_by_handle(handle: String) -> Result<Principal, String> {
+    get_principal_by_handle(&handle)
+        .ok_or_else(|| "User not found".to_string())
+}
+
+pub fn get_public_profile(handle: String) -> Result<PublicProfile, String> {
+    get_profile(get_user_by_handle(handle)?)
+}
+
+pub fn get_profile(principal: Principal) -> Result<PublicProfile, String> {
+    get_user_by_principal(resolve_principal(principal))
+        .map(|user| to_public_profile(&user))
+        .ok_or_else(|| "User not found".to_string())
+}
+
+pub fn update_privacy_settings(settings: PrivacySettings) -> Result<User, String> {
+    let principal = require_active()?;
+
+    let mut user = get_user_by_principal(principal)
+        .ok_or_else(|| "User not found".to_string())?;
+
+    user.privacy = settings;
+    user.updated_at = time();
+
+    save_user(user.clone());
+    Ok(user)
+}
+
+/// Projects a `User` onto the fields other callers may see, honoring the
+/// user's privacy settings.
+pub fn to_public_profile(user: &User) -> PublicProfile {
+    let privacy = &user.privacy;
+
+    PublicProfile {
+        principal: user.principal,
+        handle: user.handle.clone(),
+        name: user.name.clone(),
+        avatar_url: user.avatar_url.clone(),
+        email: if privacy.show_email { Some(user.email.clone()) } else { None },
+        bio: if privacy.show_bio { user.bio.clone() } else { None },
+        links: if privacy.show_links { user.links.clone() } else { vec![] },
+        location: if privacy.show_location { user.location.clone() } else { None },
+        follower_count: user.follower_count,
+        following_count: user.following_count,
+        created_at: user.created_at,
+    }
+}
+
+/// Maps a linked device principal to the account it belongs to. Principals
+/// that aren't linked are returned unchanged.
+pub fn resolve_principal(principal: Principal) -> Principal {
+    get_linked_principal(principal)
+        .map(|link| link.account)
+        .unwrap_or(principal)
+}
+
+/// The caller's canonical principal for read access checks. Unlike
+/// `require_authenticated` this never fails and may return the anonymous
+/// principal.
+pub fn caller_principal() -> Principal {
+    resolve_principal(caller())
+}
+
+/// Returns the caller's canonical account principal, resolving linked devices.
 pub fn require_authenticated() -> Result<Principal, String> {
-    let principal = caller();
+    let principal = resolve_principal(caller());
     
     if principal == Principal::anonymous() {
         return Err("Authentication required".to_string());
@@ -50,4 +258,257 @@ pub fn require_authenticated() -> Result<Principal, String> {
         .ok_or_else(|| "User not registered".to_string())?;
 
     Ok(principal)
-}
\ No newline at end of file
+}
+
+/// Like `require_authenticated`, but also rejects suspended or banned
+/// principals. Use this for every call that changes state.
+pub fn require_active() -> Result<Principal, String> {
+    let principal = require_authenticated()?;
+    ensure_not_suspended(principal, time())?;
+    Ok(principal)
+}
+
+fn ensure_not_suspended(principal: Principal, now: u64) -> Result<(), String> {
+    if let Some(suspension) = get_suspension_by_principal(principal) {
+        // Expired suspensions are cleaned up by a timer; don't wait for it
+        let expired = suspension.expires_at.is_some_and(|expires_at| expires_at <= now);
+
+        if !expired {
+            return Err(match suspension.kind {
+                SuspensionKind::Banned => format!("Account banned: {}", suspension.reason),
+                SuspensionKind::Suspended => format!("Account suspended: {}", suspension.reason),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// The account a call acts for, and the delegate that made it if the
+/// account holder didn't call directly.
+pub struct Actor {
+    pub account: Principal,
+    pub delegate: Option<Principal>,
+}
+
+/// Accepts either an active account holder or a delegate holding `scope`
+/// on behalf of an active account.
+pub fn require_scope(scope: Scope) -> Result<Actor, String> {
+    let principal = caller();
+
+    let Some(delegation) = get_delegation(principal) else {
+        let account = require_active()?;
+        return Ok(Actor { account, delegate: None });
+    };
+
+    act_as_delegate(delegation, scope, time())
+}
+
+/// Checks that a delegation is live and covers `scope`, and records its use.
+fn act_as_delegate(mut delegation: Delegation, scope: Scope, now: u64) -> Result<Actor, String> {
+    if delegation.revoked_at.is_some() {
+        return Err("Delegation has been revoked".to_string());
+    }
+
+    if delegation.expires_at <= now {
+        return Err("Delegation has expired".to_string());
+    }
+
+    if !delegation.scopes.contains(&scope) {
+        return Err(format!("Delegation is missing the {:?} scope", scope));
+    }
+
+    ensure_not_suspended(delegation.account, now)?;
+
+    delegation.last_used_at = Some(now);
+    let actor = Actor { account: delegation.account, delegate: Some(delegation.delegate) };
+    save_delegation(delegation);
+
+    Ok(actor)
+}
+
+pub fn get_role(principal: Principal) -> Role {
+    get_role_by_principal(principal).unwrap_or(Role::User)
+}
+
+/// Requires the caller to hold `minimum` or a more privileged role.
+/// Privileged principals don't need a registered `User` record.
+pub fn require_role(minimum: Role) -> Result<Principal, String> {
+    let principal = resolve_principal(caller());
+
+    if principal == Principal::anonymous() {
+        return Err("Authentication required".to_string());
+    }
+
+    if get_role(principal) < minimum {
+        return Err(format!("{:?} role required", minimum));
+    }
+
+    Ok(principal)
+}
+
+pub fn require_admin() -> Result<Principal, String> {
+    require_role(Role::Admin)
+}
+
+pub fn require_moderator() -> Result<Principal, String> {
+    require_role(Role::Moderator)
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::{BoundedStorable, Storable};
+    use super::*;
+    use crate::storage::{save_linked_principal, save_suspension};
+    use crate::types::{LinkedPrincipal, Suspension};
+
+    fn principal(id: u8) -> Principal {
+        Principal::from_slice(&[id])
+    }
+
+    fn register(principal: Principal) -> User {
+        let user = new_user(principal, Some("Aurora".to_string()), "aurora@example.com".to_string(), 42).unwrap();
+        save_user(user.clone());
+        user
+    }
+
+    #[test]
+    fn registration_builds_an_unverified_account() {
+        let user = new_user(principal(1), Some("Aurora".to_string()), "aurora@example.com".to_string(), 42).unwrap();
+
+        assert_eq!(user.principal, principal(1));
+        assert_eq!(user.email, "aurora@example.com");
+        assert_eq!(user.email_status, EmailStatus::Unverified);
+        assert_eq!(user.handle, None);
+        assert_eq!((user.created_at, user.updated_at), (42, 42));
+    }
+
+    #[test]
+    fn anonymous_principals_cannot_register() {
+        let result = new_user(Principal::anonymous(), None, "aurora@example.com".to_string(), 42);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn registration_validates_name_and_email() {
+        assert!(new_user(principal(1), Some(" ".to_string()), "aurora@example.com".to_string(), 42).is_err());
+        assert!(new_user(principal(1), None, "aurora".to_string(), 42).is_err());
+    }
+
+    #[test]
+    fn a_profile_at_every_limit_fits_the_user_bound() {
+        // Quotes are stored escaped and emoji take four bytes, so each field is as large as it gets
+        let mut user = new_user(
+            Principal::from_slice(&[255; 29]),
+            Some("🎸".repeat(64)),
+            format!("{}@example.com", "\"".repeat(242)),
+            42,
+        )
+        .unwrap();
+
+        let handle = format!("a{}", "1".repeat(29));
+        let avatar_url = format!("https://example.com/{}", "a".repeat(492));
+        let bio = "\"".repeat(1000);
+        let links = vec![format!("https://example.com/{}", "a".repeat(236)); 10];
+        let location = "🎸".repeat(100);
+
+        validation::validate_handle(&handle).unwrap();
+        validation::validate_avatar_url(&avatar_url).unwrap();
+        validation::validate_bio(&bio).unwrap();
+        validation::validate_links(&links).unwrap();
+        validation::validate_location(&location).unwrap();
+
+        user.handle = Some(handle);
+        user.handle_updated_at = Some(u64::MAX);
+        user.email_verified_at = Some(u64::MAX);
+        user.avatar_url = Some(avatar_url);
+        user.bio = Some(bio);
+        user.links = links;
+        user.location = Some(location);
+        user.follower_count = u64::MAX;
+        user.following_count = u64::MAX;
+
+        let size = user.to_bytes().len();
+        assert!(size <= User::MAX_SIZE as usize, "{} bytes", size);
+
+        save_user(user.clone());
+        assert_eq!(get_user_by_principal(user.principal).unwrap().bio, user.bio);
+    }
+
+    #[test]
+    fn principals_register_only_once() {
+        register(principal(1));
+
+        let result = new_user(principal(1), None, "other@example.com".to_string(), 43);
+        assert_eq!(result.unwrap_err(), "User already registered");
+    }
+
+    #[test]
+    fn linked_principals_cannot_register_and_share_the_account_profile() {
+        register(principal(1));
+        save_linked_principal(LinkedPrincipal { principal: principal(2), account: principal(1), linked_at: 42 });
+
+        let result = new_user(principal(2), None, "other@example.com".to_string(), 43);
+        assert_eq!(result.unwrap_err(), "Principal is linked to an existing account");
+
+        assert_eq!(get_profile(principal(2)).unwrap().principal, principal(1));
+    }
+
+    /// Principal 9 may write tracks for account 1 until time 100.
+    fn delegation() -> Delegation {
+        Delegation {
+            delegate: principal(9),
+            account: principal(1),
+            scopes: vec![Scope::TracksWrite],
+            created_at: 0,
+            expires_at: 100,
+            revoked_at: None,
+            last_used_at: None,
+        }
+    }
+
+    #[test]
+    fn delegates_act_for_the_account_within_their_scopes() {
+        let actor = act_as_delegate(delegation(), Scope::TracksWrite, 50).unwrap();
+
+        assert_eq!(actor.account, principal(1));
+        assert_eq!(actor.delegate, Some(principal(9)));
+        assert_eq!(get_delegation(principal(9)).unwrap().last_used_at, Some(50));
+    }
+
+    #[test]
+    fn delegates_are_refused_outside_their_scopes() {
+        let result = act_as_delegate(delegation(), Scope::NftsCreate, 50);
+
+        assert_eq!(result.err().unwrap(), "Delegation is missing the NftsCreate scope");
+        assert!(get_delegation(principal(9)).is_none());
+    }
+
+    #[test]
+    fn revoked_and_expired_delegations_are_refused() {
+        let revoked = Delegation { revoked_at: Some(10), ..delegation() };
+        let result = act_as_delegate(revoked, Scope::TracksWrite, 50);
+        assert_eq!(result.err().unwrap(), "Delegation has been revoked");
+
+        let result = act_as_delegate(delegation(), Scope::TracksWrite, 100);
+        assert_eq!(result.err().unwrap(), "Delegation has expired");
+    }
+
+    #[test]
+    fn delegates_of_a_suspended_account_are_refused() {
+        save_suspension(Suspension {
+            principal: principal(1),
+            kind: SuspensionKind::Suspended,
+            reason: "Spam".to_string(),
+            issued_by: principal(2),
+            issued_at: 0,
+            expires_at: Some(60),
+        });
+
+        let result = act_as_delegate(delegation(), Scope::TracksWrite, 50);
+        assert_eq!(result.err().unwrap(), "Account suspended: Spam");
+
+        // The suspension no longer applies once it has expired
+        assert!(act_as_delegate(delegation(), Scope::TracksWrite, 60).is_ok());
+    }
+}
@@ -1,71 +1,291 @@
+use std::time::Duration;
 use candid::Principal;
 use ic_cdk::api::time;
 use uuid::Uuid;
-use crate::types::Collaboration;
-use crate::storage::{get_collaboration_by_id, save_collaboration, remove_collaboration, get_all_collaborations, get_project_by_id, save_project};
-use crate::auth::require_authenticated;
+use crate::types::{ActivityKind, Collaboration, CollaborationPage, Invitation, InvitationStatus, PageRequest, Project};
+use crate::storage::{get_collaboration_by_id, save_collaboration, remove_collaboration, get_collaborations_by_project, get_project_by_id, scan_project_collaborations};
+use crate::storage::{
+    get_user_by_principal, get_invitation_by_id, save_invitation, get_invitations_by_invitee,
+    get_invitations_by_project, get_all_invitations,
+};
+use crate::history::save_project_with_history;
+use crate::activity;
+use crate::pagination::{encode_cursor, resolve_page};
+use crate::validation;
+use crate::auth::{require_active, require_authenticated, caller_principal, resolve_principal};
+use crate::projects::{ensure_not_archived, is_project_owner, get_visible_project};
 
-pub fn add_collaborator(
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const DEFAULT_INVITATION_SECONDS: u64 = 7 * 24 * 60 * 60;
+const MAX_INVITATION_SECONDS: u64 = 30 * 24 * 60 * 60;
+
+/// Keeps `Project.collaborators` within the project's storage bound.
+const MAX_COLLABORATORS: usize = 20;
+
+/// Offers `invitee` a role and split on the project. Nothing changes on the
+/// project until they accept.
+pub fn invite_collaborator(
     project_id: String,
-    user_principal: Principal,
+    invitee: Principal,
     contribution_percentage: u8,
     role: String,
-) -> Result<Collaboration, String> {
-    let caller = require_authenticated()?;
-    
-    let mut project = get_project_by_id(&project_id)
+    duration_seconds: Option<u64>,
+) -> Result<Invitation, String> {
+    let caller = require_active()?;
+    let invitee = resolve_principal(invitee);
+
+    let project = get_project_by_id(&project_id)
         .ok_or_else(|| "Project not found".to_string())?;
 
-    // Only project owner can add collaborators
-    if project.owner != caller {
-        return Err("Only project owner can add collaborators".to_string());
+    // Only project owner can invite collaborators
+    if !is_project_owner(&project, caller) {
+        return Err("Only project owner can invite collaborators".to_string());
     }
 
+    ensure_not_archived(&project)?;
+
     if contribution_percentage > 100 {
         return Err("Contribution percentage cannot exceed 100%".to_string());
     }
 
-    // Check if user is already a collaborator
-    if project.collaborators.contains(&user_principal.to_string()) {
+    validation::validate_role(&role)?;
+
+    if invitee == caller {
+        return Err("Cannot invite yourself".to_string());
+    }
+
+    if get_user_by_principal(invitee).is_none() {
+        return Err("Invitee is not a registered user".to_string());
+    }
+
+    if project.collaborators.contains(&invitee.to_string()) {
         return Err("User is already a collaborator".to_string());
     }
 
-    let collaboration = Collaboration {
+    ensure_collaborator_capacity(&project)?;
+
+    let already_invited = get_invitations_by_project(&project_id)
+        .iter()
+        .any(|invitation| invitation.invitee == invitee && invitation.status == InvitationStatus::Pending);
+    if already_invited {
+        return Err("User already has a pending invitation".to_string());
+    }
+
+    let duration = duration_seconds.unwrap_or(DEFAULT_INVITATION_SECONDS);
+    if duration == 0 || duration > MAX_INVITATION_SECONDS {
+        return Err("Invitation duration must be between 1 second and 30 days".to_string());
+    }
+
+    let now = time();
+    let invitation = Invitation {
         id: Uuid::new_v4().to_string(),
-        project_id: project_id.clone(),
-        user_principal,
+        project_id,
+        invitee,
+        invited_by: caller,
+        role: role.trim().to_string(),
         contribution_percentage,
-        role,
-        joined_at: time(),
+        status: InvitationStatus::Pending,
+        created_at: now,
+        expires_at: now + duration * NANOS_PER_SECOND,
+        responded_at: None,
     };
 
-    project.collaborators.push(user_principal.to_string());
-    project.updated_at = time();
+    save_invitation(invitation.clone());
+    schedule_expiry(&invitation);
+    Ok(invitation)
+}
+
+/// Joins the project on the invitation's terms.
+pub fn accept_invitation(id: String) -> Result<Collaboration, String> {
+    let caller = require_active()?;
+    let mut invitation = get_pending_invitation(&id)?;
+
+    if invitation.invitee != caller {
+        return Err("Only the invitee can accept this invitation".to_string());
+    }
+
+    let mut project = get_project_by_id(&invitation.project_id)
+        .ok_or_else(|| "Project not found".to_string())?;
+
+    ensure_not_archived(&project)?;
+
+    if project.collaborators.contains(&caller.to_string()) {
+        return Err("User is already a collaborator".to_string());
+    }
+
+    // Other invitations may have been accepted since this one was sent
+    ensure_collaborator_capacity(&project)?;
+
+    let now = time();
+    let collaboration = Collaboration {
+        id: Uuid::new_v4().to_string(),
+        project_id: project.id.clone(),
+        user_principal: caller,
+        contribution_percentage: invitation.contribution_percentage,
+        role: invitation.role.clone(),
+        joined_at: now,
+    };
 
+    project.collaborators.push(caller.to_string());
+    project.updated_at = now;
+
+    invitation.status = InvitationStatus::Accepted;
+    invitation.responded_at = Some(now);
+
+    save_invitation(invitation);
     save_collaboration(collaboration.clone());
-    save_project(project);
-    
+    save_project_with_history(project, caller);
+    activity::record(&collaboration.project_id, caller, ActivityKind::CollaboratorJoined { principal: caller });
+
     Ok(collaboration)
 }
 
-pub fn get_project_collaborators(project_id: String) -> Vec<Collaboration> {
-    get_all_collaborations()
+pub fn decline_invitation(id: String) -> Result<Invitation, String> {
+    let caller = require_active()?;
+    let invitation = get_pending_invitation(&id)?;
+
+    if invitation.invitee != caller {
+        return Err("Only the invitee can decline this invitation".to_string());
+    }
+
+    Ok(close_invitation(invitation, InvitationStatus::Declined))
+}
+
+pub fn revoke_invitation(id: String) -> Result<Invitation, String> {
+    let caller = require_active()?;
+    let invitation = get_pending_invitation(&id)?;
+
+    let project = get_project_by_id(&invitation.project_id)
+        .ok_or_else(|| "Project not found".to_string())?;
+
+    if !is_project_owner(&project, caller) {
+        return Err("Only project owner can revoke invitations".to_string());
+    }
+
+    Ok(close_invitation(invitation, InvitationStatus::Revoked))
+}
+
+/// The caller's pending invitations.
+pub fn get_my_invitations() -> Result<Vec<Invitation>, String> {
+    let caller = require_authenticated()?;
+
+    Ok(get_invitations_by_invitee(caller)
         .into_iter()
-        .filter(|collaboration| collaboration.project_id == project_id)
-        .collect()
+        .filter(|invitation| invitation.status == InvitationStatus::Pending)
+        .collect())
 }
 
-pub fn remove_collaborator(project_id: String, collaboration_id: String) -> Result<(), String> {
+/// Every invitation sent for a project, including answered ones.
+pub fn get_project_invitations(project_id: String) -> Result<Vec<Invitation>, String> {
     let caller = require_authenticated()?;
+
+    let project = get_project_by_id(&project_id)
+        .ok_or_else(|| "Project not found".to_string())?;
+
+    if !is_project_owner(&project, caller) {
+        return Err("Only project owner can view invitations".to_string());
+    }
+
+    Ok(get_invitations_by_project(&project_id))
+}
+
+/// Timers don't survive upgrades, so this re-arms one per pending
+/// invitation. Overdue invitations expire right away.
+pub fn schedule_invitation_expiries() {
+    for invitation in get_all_invitations()
+        .into_iter()
+        .filter(|invitation| invitation.status == InvitationStatus::Pending)
+    {
+        schedule_expiry(&invitation);
+    }
+}
+
+fn schedule_expiry(invitation: &Invitation) {
+    let delay = Duration::from_nanos(invitation.expires_at.saturating_sub(time()));
+    let id = invitation.id.clone();
+
+    ic_cdk_timers::set_timer(delay, move || expire_invitation(&id));
+}
+
+fn expire_invitation(id: &str) {
+    // It may have been answered, or removed with its project, since the timer was set
+    if let Some(invitation) = get_invitation_by_id(id) {
+        if invitation.status == InvitationStatus::Pending {
+            close_invitation(invitation, InvitationStatus::Expired);
+        }
+    }
+}
+
+/// Looks up an invitation that can still be answered. Expiry is checked
+/// here too, in case the timer hasn't fired yet.
+fn get_pending_invitation(id: &str) -> Result<Invitation, String> {
+    let invitation = get_invitation_by_id(id)
+        .ok_or_else(|| "Invitation not found".to_string())?;
+
+    if invitation.status != InvitationStatus::Pending {
+        return Err(format!("Invitation is no longer pending ({:?})", invitation.status));
+    }
+
+    if invitation.expires_at <= time() {
+        close_invitation(invitation, InvitationStatus::Expired);
+        return Err("Invitation has expired".to_string());
+    }
+
+    Ok(invitation)
+}
+
+fn close_invitation(mut invitation: Invitation, status: InvitationStatus) -> Invitation {
+    invitation.status = status;
+    invitation.responded_at = Some(time());
+    save_invitation(invitation.clone());
+    invitation
+}
+
+pub fn ensure_collaborator_capacity(project: &Project) -> Result<(), String> {
+    if project.collaborators.len() >= MAX_COLLABORATORS {
+        return Err(format!("A project cannot have more than {} collaborators", MAX_COLLABORATORS));
+    }
+
+    Ok(())
+}
+
+pub fn get_project_collaborators(project_id: String) -> Vec<Collaboration> {
+    if get_visible_project(&project_id, caller_principal()).is_none() {
+        return vec![];
+    }
+
+    get_collaborations_by_project(&project_id)
+}
+
+/// Paginated alternative to `get_project_collaborators`.
+pub fn list_project_collaborators(project_id: String, page: PageRequest) -> Result<CollaborationPage, String> {
+    if get_visible_project(&project_id, caller_principal()).is_none() {
+        return Err("Project not found".to_string());
+    }
+
+    let (after, limit) = resolve_page(&page)?;
+
+    let page = scan_project_collaborations(&project_id, after, limit);
+
+    Ok(CollaborationPage {
+        items: page.items,
+        next_cursor: page.last_key.map(|key| encode_cursor(&key)),
+    })
+}
+
+pub fn remove_collaborator(project_id: String, collaboration_id: String) -> Result<(), String> {
+    let caller = require_active()?;
     
     let mut project = get_project_by_id(&project_id)
         .ok_or_else(|| "Project not found".to_string())?;
 
     // Only project owner can remove collaborators
-    if project.owner != caller {
+    if !is_project_owner(&project, caller) {
         return Err("Only project owner can remove collaborators".to_string());
     }
 
+    ensure_not_archived(&project)?;
+
     let collaboration = get_collaboration_by_id(&collaboration_id)
         .ok_or_else(|| "Collaboration not found".to_string())?;
 
@@ -78,7 +298,56 @@ pub fn remove_collaborator(project_id: String, collaboration_id: String) -> Resu
     project.updated_at = time();
 
     remove_collaboration(&collaboration_id);
-    save_project(project);
+    save_project_with_history(project, caller);
+    activity::record(&project_id, caller, ActivityKind::CollaboratorRemoved { principal: collaboration.user_principal });
     
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::{BoundedStorable, Storable};
+    use super::*;
+
+    #[test]
+    fn roles_must_be_short_non_empty_text() {
+        assert!(validation::validate_role(" Producer ").is_ok());
+
+        assert!(validation::validate_role("  ").is_err());
+        assert!(validation::validate_role(&"a".repeat(33)).is_err());
+        assert!(validation::validate_role("Mix\nengineer").is_err());
+    }
+
+    #[test]
+    fn an_invitation_and_collaboration_with_the_longest_role_fit_their_bounds() {
+        // Emoji take four bytes, the most a character can
+        let role = "🎸".repeat(32);
+        validation::validate_role(&role).unwrap();
+
+        let principal = Principal::from_slice(&[255; 29]);
+        let invitation = Invitation {
+            id: Uuid::nil().to_string(),
+            project_id: Uuid::nil().to_string(),
+            invitee: principal,
+            invited_by: principal,
+            role: role.clone(),
+            contribution_percentage: 100,
+            status: InvitationStatus::Declined,
+            created_at: u64::MAX,
+            expires_at: u64::MAX,
+            responded_at: Some(u64::MAX),
+        };
+        let collaboration = Collaboration {
+            id: Uuid::nil().to_string(),
+            project_id: Uuid::nil().to_string(),
+            user_principal: principal,
+            contribution_percentage: 100,
+            role,
+            joined_at: u64::MAX,
+        };
+
+        let size = invitation.to_bytes().len();
+        assert!(size <= Invitation::MAX_SIZE as usize, "{} bytes", size);
+        let size = collaboration.to_bytes().len();
+        assert!(size <= Collaboration::MAX_SIZE as usize, "{} bytes", size);
+    }
+}
@@ -0,0 +1,145 @@
+use candid::Principal;
+use ic_cdk::api::time;
+use crate::types::{Delegation, Scope};
+use crate::storage::{
+    get_user_by_principal, get_linked_principal, get_delegation, save_delegation,
+    get_delegations_for_account,
+};
+use crate::auth::require_active;
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const MAX_DELEGATION_SECONDS: u64 = 365 * 24 * 60 * 60;
+
+/// Authorizes `delegate` to act for the caller within `scopes` until the
+/// delegation expires or is revoked. Re-authorizing an existing delegate
+/// replaces its scopes and expiry.
+pub fn authorize_delegate(
+    delegate: Principal,
+    scopes: Vec<Scope>,
+    duration_seconds: u64,
+) -> Result<Delegation, String> {
+    let delegation = new_delegation(require_active()?, delegate, scopes, duration_seconds, time())?;
+    save_delegation(delegation.clone());
+    Ok(delegation)
+}
+
+/// Checks that `account` may grant `delegate` these scopes and builds the
+/// unsaved delegation.
+fn new_delegation(
+    account: Principal,
+    delegate: Principal,
+    scopes: Vec<Scope>,
+    duration_seconds: u64,
+    now: u64,
+) -> Result<Delegation, String> {
+    if delegate == Principal::anonymous() || delegate == account {
+        return Err("Invalid delegate principal".to_string());
+    }
+
+    if get_user_by_principal(delegate).is_some() || get_linked_principal(delegate).is_some() {
+        return Err("Delegate must not be a registered or linked principal".to_string());
+    }
+
+    if scopes.is_empty() {
+        return Err("At least one scope is required".to_string());
+    }
+
+    if duration_seconds == 0 || duration_seconds > MAX_DELEGATION_SECONDS {
+        return Err("Delegation must last between 1 second and 365 days".to_string());
+    }
+
+    // A delegate acts for exactly one account; a live grant can't be taken over
+    if let Some(existing) = get_delegation(delegate) {
+        let active = existing.revoked_at.is_none() && existing.expires_at > now;
+        if existing.account != account && active {
+            return Err("Principal is already a delegate for another account".to_string());
+        }
+    }
+
+    let mut unique_scopes = Vec::new();
+    for scope in scopes {
+        if !unique_scopes.contains(&scope) {
+            unique_scopes.push(scope);
+        }
+    }
+
+    Ok(Delegation {
+        delegate,
+        account,
+        scopes: unique_scopes,
+        created_at: now,
+        expires_at: now + duration_seconds * NANOS_PER_SECOND,
+        revoked_at: None,
+        last_used_at: None,
+    })
+}
+
+pub fn revoke_delegate(delegate: Principal) -> Result<Delegation, String> {
+    let account = require_active()?;
+
+    let mut delegation = get_delegation(delegate)
+        .ok_or_else(|| "Delegation not found".to_string())?;
+
+    if delegation.account != account {
+        return Err("Delegation belongs to another account".to_string());
+    }
+
+    if delegation.revoked_at.is_some() {
+        return Err("Delegation already revoked".to_string());
+    }
+
+    delegation.revoked_at = Some(time());
+    save_delegation(delegation.clone());
+    Ok(delegation)
+}
+
+pub fn list_delegates() -> Result<Vec<Delegation>, String> {
+    let account = require_active()?;
+    Ok(get_delegations_for_account(account))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principal(id: u8) -> Principal {
+        Principal::from_slice(&[id])
+    }
+
+    #[test]
+    fn delegations_keep_each_scope_once_and_expire_after_the_duration() {
+        let scopes = vec![Scope::TracksWrite, Scope::NftsCreate, Scope::TracksWrite];
+        let delegation = new_delegation(principal(1), principal(9), scopes, 60, 5).unwrap();
+
+        assert_eq!(delegation.scopes, [Scope::TracksWrite, Scope::NftsCreate]);
+        assert_eq!(delegation.created_at, 5);
+        assert_eq!(delegation.expires_at, 5 + 60 * NANOS_PER_SECOND);
+    }
+
+    #[test]
+    fn delegations_need_a_scope_and_a_bounded_duration() {
+        assert!(new_delegation(principal(1), principal(9), vec![], 60, 0).is_err());
+        assert!(new_delegation(principal(1), principal(9), vec![Scope::TracksWrite], 0, 0).is_err());
+
+        let too_long = MAX_DELEGATION_SECONDS + 1;
+        assert!(new_delegation(principal(1), principal(9), vec![Scope::TracksWrite], too_long, 0).is_err());
+    }
+
+    #[test]
+    fn accounts_cannot_delegate_to_themselves_or_anonymous() {
+        assert!(new_delegation(principal(1), principal(1), vec![Scope::TracksWrite], 60, 0).is_err());
+        assert!(new_delegation(principal(1), Principal::anonymous(), vec![Scope::TracksWrite], 60, 0).is_err());
+    }
+
+    #[test]
+    fn a_live_delegate_cannot_be_taken_over_by_another_account() {
+        save_delegation(new_delegation(principal(1), principal(9), vec![Scope::TracksWrite], 60, 0).unwrap());
+
+        let result = new_delegation(principal(2), principal(9), vec![Scope::NftsCreate], 60, 0);
+        assert_eq!(result.unwrap_err(), "Principal is already a delegate for another account");
+
+        // The owning account may replace its own grant, and anyone may reuse an expired one
+        assert!(new_delegation(principal(1), principal(9), vec![Scope::NftsCreate], 60, 0).is_ok());
+        assert!(new_delegation(principal(2), principal(9), vec![Scope::NftsCreate], 60, 60 * NANOS_PER_SECOND).is_ok());
+    }
+}
@@ -0,0 +1,347 @@
+use candid::Principal;
+use ic_cdk::{caller, api::time};
+use sha2::{Digest, Sha256};
+use crate::types::{EmailStatus, EmailVerification, OutboundEmail, User};
+use crate::storage::{
+    get_user_by_principal, save_user, get_email_verification, save_email_verification,
+    remove_email_verification, push_outbound_email, get_outbound_emails, remove_outbound_email,
+    is_mailer, save_mailer, remove_mailer as remove_stored_mailer, get_mailers,
+};
+use crate::auth::{require_active, require_admin};
+use crate::random;
+
+const VERIFICATION_CODE_TTL_NS: u64 = 30 * 60 * 1_000_000_000;
+const RESEND_COOLDOWN_NS: u64 = 60 * 1_000_000_000;
+const MAX_VERIFICATION_ATTEMPTS: u8 = 5;
+const MAX_OUTBOX_BATCH: usize = 100;
+
+/// Delivers transactional email. The canister can't reach SMTP directly, so
+/// the production implementation is `OutboxMailer`; another implementation
+/// can be swapped in through `mailer` once an HTTPS outcall provider is
+/// available.
+pub trait Mailer {
+    fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), String>;
+}
+
+/// Queues messages in stable memory for an off-chain mailer to poll via
+/// `get_pending_emails` and acknowledge via `ack_emails`.
+pub struct OutboxMailer;
+
+impl Mailer for OutboxMailer {
+    fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), String> {
+        push_outbound_email(OutboundEmail {
+            id: 0,
+            to: to.to_string(),
+            subject: subject.to_string(),
+            body: body.to_string(),
+            created_at: time(),
+        });
+        Ok(())
+    }
+}
+
+/// The mailer every message goes through.
+pub fn mailer() -> &'static dyn Mailer {
+    &OutboxMailer
+}
+
+/// Generates a fresh code for `user.email`, replacing any outstanding one,
+/// and marks the address as pending. The caller is responsible for saving
+/// `user`.
+pub fn issue_verification_code(user: &mut User, mailer: &dyn Mailer, now: u64) -> Result<(), String> {
+    let code = generate_code()?;
+
+    let verification = EmailVerification {
+        principal: user.principal,
+        email: user.email.clone(),
+        code_hash: hash_code(&user.email, &code),
+        attempts: 0,
+        created_at: now,
+        expires_at: now + VERIFICATION_CODE_TTL_NS,
+    };
+
+    mailer.send(
+        &user.email,
+        "Verify your NFTune email address",
+        &format!(
+            "Your NFTune verification code is {}. It expires in 30 minutes.",
+            code
+        ),
+    )?;
+
+    save_email_verification(verification);
+    user.email_status = EmailStatus::Pending;
+    Ok(())
+}
+
+pub fn request_email_verification() -> Result<User, String> {
+    let principal = require_active()?;
+
+    let mut user = get_user_by_principal(principal)
+        .ok_or_else(|| "User not found".to_string())?;
+
+    if user.email_status == EmailStatus::Verified {
+        return Err("Email is already verified".to_string());
+    }
+
+    let now = time();
+    if let Some(existing) = get_email_verification(principal) {
+        if now < existing.created_at + RESEND_COOLDOWN_NS {
+            return Err("Please wait a minute before requesting another code".to_string());
+        }
+    }
+
+    issue_verification_code(&mut user, mailer(), now)?;
+    save_user(user.clone());
+    Ok(user)
+}
+
+pub fn verify_email(code: String) -> Result<User, String> {
+    let principal = require_active()?;
+
+    let mut user = get_user_by_principal(principal)
+        .ok_or_else(|| "User not found".to_string())?;
+
+    confirm_email(&mut user, &code, time())?;
+
+    save_user(user.clone());
+    Ok(user)
+}
+
+/// Checks `code` against the user's outstanding verification and marks the
+/// address verified. Failed attempts are counted, and expired or exhausted
+/// codes are discarded. The caller is responsible for saving `user`.
+fn confirm_email(user: &mut User, code: &str, now: u64) -> Result<(), String> {
+    let principal = user.principal;
+
+    let mut verification = get_email_verification(principal)
+        .ok_or_else(|| "No verification pending".to_string())?;
+
+    // The address changed after the code was issued
+    if verification.email != user.email {
+        remove_email_verification(principal);
+        return Err("Verification code is for a different email address".to_string());
+    }
+
+    if verification.expires_at <= now {
+        remove_email_verification(principal);
+        return Err("Verification code has expired".to_string());
+    }
+
+    if verification.attempts >= MAX_VERIFICATION_ATTEMPTS {
+        remove_email_verification(principal);
+        return Err("Too many attempts, request a new code".to_string());
+    }
+
+    if hash_code(&user.email, code.trim()) != verification.code_hash {
+        verification.attempts += 1;
+        save_email_verification(verification);
+        return Err("Invalid verification code".to_string());
+    }
+
+    remove_email_verification(principal);
+
+    user.email_status = EmailStatus::Verified;
+    user.email_verified_at = Some(now);
+    user.updated_at = now;
+    Ok(())
+}
+
+/// Returns the oldest undelivered messages, for the off-chain mailer.
+pub fn get_pending_emails(limit: u32) -> Result<Vec<OutboundEmail>, String> {
+    require_mailer()?;
+    Ok(get_outbound_emails((limit as usize).min(MAX_OUTBOX_BATCH)))
+}
+
+/// Removes delivered messages from the outbox.
+pub fn ack_emails(ids: Vec<u64>) -> Result<(), String> {
+    require_mailer()?;
+
+    for id in ids {
+        remove_outbound_email(id);
+    }
+
+    Ok(())
+}
+
+/// Lets `principal` read and acknowledge the outbox without holding any
+/// other privileges.
+pub fn add_mailer(principal: Principal) -> Result<(), String> {
+    require_admin()?;
+
+    if principal == Principal::anonymous() {
+        return Err("The anonymous principal cannot be a mailer".to_string());
+    }
+
+    save_mailer(principal, time());
+    Ok(())
+}
+
+pub fn remove_mailer(principal: Principal) -> Result<(), String> {
+    require_admin()?;
+    remove_stored_mailer(principal);
+    Ok(())
+}
+
+pub fn list_mailers() -> Result<Vec<Principal>, String> {
+    require_admin()?;
+    Ok(get_mailers())
+}
+
+/// The outbox is open to allow-listed mailer principals, and to admins so
+/// a mailer that still runs as an admin keeps working.
+fn require_mailer() -> Result<(), String> {
+    if is_mailer(caller()) {
+        return Ok(());
+    }
+
+    require_admin()
+        .map(|_| ())
+        .map_err(|_| "Mailer access required".to_string())
+}
+
+fn generate_code() -> Result<String, String> {
+    let bytes = random::random_bytes()?;
+    let value = u64::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]);
+    Ok(format!("{:06}", value % 1_000_000))
+}
+
+fn hash_code(email: &str, code: &str) -> String {
+    Sha256::digest(format!("{}:{}", email, code).as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use super::*;
+    use crate::types::PrivacySettings;
+
+    /// Keeps sent messages in memory instead of queueing them.
+    #[derive(Default)]
+    struct RecordingMailer {
+        sent: RefCell<Vec<(String, String)>>,
+    }
+
+    impl Mailer for RecordingMailer {
+        fn send(&self, to: &str, _subject: &str, body: &str) -> Result<(), String> {
+            self.sent.borrow_mut().push((to.to_string(), body.to_string()));
+            Ok(())
+        }
+    }
+
+    struct FailingMailer;
+
+    impl Mailer for FailingMailer {
+        fn send(&self, _to: &str, _subject: &str, _body: &str) -> Result<(), String> {
+            Err("Mailer unavailable".to_string())
+        }
+    }
+
+    fn user() -> User {
+        User {
+            principal: Principal::from_slice(&[1]),
+            handle: None,
+            handle_updated_at: None,
+            name: None,
+            email: "aurora@example.com".to_string(),
+            email_status: EmailStatus::Unverified,
+            email_verified_at: None,
+            avatar_url: None,
+            bio: None,
+            links: vec![],
+            location: None,
+            privacy: PrivacySettings::default(),
+            follower_count: 0,
+            following_count: 0,
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    /// Issues a code at time 0 and returns it as mailed.
+    fn issue(user: &mut User) -> String {
+        random::mix_seed(&[7; 32]);
+        let mailer = RecordingMailer::default();
+        issue_verification_code(user, &mailer, 0).unwrap();
+
+        let (_, body) = mailer.sent.borrow()[0].clone();
+        body.chars().filter(char::is_ascii_digit).take(6).collect()
+    }
+
+    #[test]
+    fn issuing_a_code_mails_it_and_marks_the_email_pending() {
+        let mut user = user();
+        let mailer = RecordingMailer::default();
+        random::mix_seed(&[7; 32]);
+
+        issue_verification_code(&mut user, &mailer, 0).unwrap();
+
+        let sent = mailer.sent.borrow();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0, "aurora@example.com");
+        assert_eq!(user.email_status, EmailStatus::Pending);
+
+        let verification = get_email_verification(user.principal).unwrap();
+        assert_eq!(verification.email, user.email);
+        assert_eq!(verification.expires_at, VERIFICATION_CODE_TTL_NS);
+    }
+
+    #[test]
+    fn a_failed_send_leaves_nothing_pending() {
+        let mut user = user();
+        random::mix_seed(&[7; 32]);
+
+        assert!(issue_verification_code(&mut user, &FailingMailer, 0).is_err());
+        assert_eq!(user.email_status, EmailStatus::Unverified);
+        assert!(get_email_verification(user.principal).is_none());
+    }
+
+    #[test]
+    fn the_mailed_code_verifies_the_email() {
+        let mut user = user();
+        let code = issue(&mut user);
+
+        confirm_email(&mut user, &format!(" {} ", code), 5).unwrap();
+
+        assert_eq!(user.email_status, EmailStatus::Verified);
+        assert_eq!(user.email_verified_at, Some(5));
+        assert!(get_email_verification(user.principal).is_none());
+    }
+
+    #[test]
+    fn wrong_codes_are_counted_until_the_code_is_discarded() {
+        let mut user = user();
+        let code = issue(&mut user);
+        let wrong = if code == "000000" { "111111" } else { "000000" };
+
+        for _ in 0..MAX_VERIFICATION_ATTEMPTS {
+            assert_eq!(confirm_email(&mut user, wrong, 1).unwrap_err(), "Invalid verification code");
+        }
+
+        assert_eq!(confirm_email(&mut user, &code, 1).unwrap_err(), "Too many attempts, request a new code");
+        assert!(get_email_verification(user.principal).is_none());
+        assert_eq!(user.email_status, EmailStatus::Pending);
+    }
+
+    #[test]
+    fn expired_codes_are_rejected() {
+        let mut user = user();
+        let code = issue(&mut user);
+
+        let result = confirm_email(&mut user, &code, VERIFICATION_CODE_TTL_NS);
+        assert_eq!(result.unwrap_err(), "Verification code has expired");
+    }
+
+    #[test]
+    fn codes_only_verify_the_address_they_were_sent_to() {
+        let mut user = user();
+        let code = issue(&mut user);
+        user.email = "other@example.com".to_string();
+
+        let result = confirm_email(&mut user, &code, 1);
+        assert_eq!(result.unwrap_err(), "Verification code is for a different email address");
+    }
+}
@@ -0,0 +1,286 @@
+use candid::Principal;
+use ic_cdk::api::time;
+use crate::types::{ActivityKind, PageRequest, Project, ProjectRevision, ProjectSnapshot, RevisionChange, RevisionPage};
+use crate::storage::{
+    get_project_by_id, save_project, get_latest_project_revision, get_project_revision,
+    save_project_revision, scan_project_revisions,
+};
+use crate::auth::{require_active, caller_principal};
+use crate::pagination::{encode_cursor, resolve_page};
+use crate::projects::{ensure_not_archived, get_visible_project, is_project_member, is_project_owner};
+use crate::validation;
+use crate::activity;
+
+/// Saves `project`, recording a revision by `author` if any versioned field
+/// changed. Use this instead of `save_project` for edits users make. A new
+/// project's first revision is a plain snapshot with no changes.
+pub fn save_project_with_history(project: Project, author: Principal) {
+    let current = snapshot(&project);
+
+    match get_project_by_id(&project.id) {
+        Some(previous) => {
+            let changes = diff(&snapshot(&previous), &current);
+            if !changes.is_empty() {
+                record_revision(&project.id, author, None, changes, current);
+            }
+        }
+        None => {
+            record_revision(&project.id, author, None, vec![], current);
+        }
+    }
+
+    save_project(project);
+}
+
+/// Revisions of a project, newest first.
+pub fn get_project_history(project_id: String, page: PageRequest) -> Result<RevisionPage, String> {
+    if get_visible_project(&project_id, caller_principal()).is_none() {
+        return Err("Project not found".to_string());
+    }
+
+    let (after, limit) = resolve_page(&page)?;
+    let page = scan_project_revisions(&project_id, after, limit);
+
+    Ok(RevisionPage {
+        items: page.items.into_iter().map(with_changes).collect(),
+        next_cursor: page.last_key.map(|key| encode_cursor(&key)),
+    })
+}
+
+/// Records the project's current state under a name such as "pre-master".
+pub fn create_project_snapshot(project_id: String, label: String) -> Result<ProjectRevision, String> {
+    let caller = require_active()?;
+
+    let project = get_project_by_id(&project_id)
+        .ok_or_else(|| "Project not found".to_string())?;
+
+    if !is_project_member(&project, caller) {
+        return Err("Only project owner or collaborators can create snapshots".to_string());
+    }
+
+    ensure_not_archived(&project)?;
+    validation::validate_snapshot_label(&label)?;
+
+    Ok(record_revision(
+        &project_id,
+        caller,
+        Some(label.trim().to_string()),
+        vec![],
+        snapshot(&project),
+    ))
+}
+
+/// Rolls the project's name, description, visibility and labels back to a
+/// revision. Tracks, collaborators, the owner and the archive state keep
+/// their current values. The rollback is recorded as a revision too.
+pub fn restore_project_revision(project_id: String, number: u64) -> Result<Project, String> {
+    let caller = require_active()?;
+
+    let mut project = get_project_by_id(&project_id)
+        .ok_or_else(|| "Project not found".to_string())?;
+
+    if !is_project_owner(&project, caller) {
+        return Err("Only project owner can restore revisions".to_string());
+    }
+
+    ensure_not_archived(&project)?;
+
+    let revision = get_project_revision(&project_id, number)
+        .ok_or_else(|| "Revision not found".to_string())?;
+    let snapshot = revision.snapshot;
+
+    project.name = snapshot.name;
+    project.description = snapshot.description;
+    project.visibility = snapshot.visibility;
+    project.genres = snapshot.genres;
+    project.moods = snapshot.moods;
+    project.tags = snapshot.tags;
+    project.updated_at = time();
+
+    save_project_with_history(project.clone(), caller);
+    activity::record(&project_id, caller, ActivityKind::ProjectUpdated);
+    Ok(project)
+}
+
+fn record_revision(
+    project_id: &str,
+    author: Principal,
+    label: Option<String>,
+    changes: Vec<RevisionChange>,
+    snapshot: ProjectSnapshot,
+) -> ProjectRevision {
+    let number = get_latest_project_revision(project_id)
+        .map_or(1, |latest| latest.number + 1);
+
+    let revision = ProjectRevision {
+        project_id: project_id.to_string(),
+        number,
+        author,
+        created_at: time(),
+        label,
+        changes,
+        snapshot,
+    };
+
+    save_project_revision(revision.clone());
+    revision
+}
+
+/// Fills in what changed since the previous revision. First revisions and
+/// named snapshots have no changes.
+fn with_changes(mut revision: ProjectRevision) -> ProjectRevision {
+    if revision.changes.is_empty() && revision.label.is_none() && revision.number > 1 {
+        if let Some(previous) = get_project_revision(&revision.project_id, revision.number - 1) {
+            revision.changes = diff(&previous.snapshot, &revision.snapshot);
+        }
+    }
+
+    revision
+}
+
+fn snapshot(project: &Project) -> ProjectSnapshot {
+    ProjectSnapshot {
+        name: project.name.clone(),
+        description: project.description.clone(),
+        visibility: project.visibility,
+        genres: project.genres.clone(),
+        moods: project.moods.clone(),
+        tags: project.tags.clone(),
+        tracks: project.tracks.clone(),
+        collaborators: project.collaborators.clone(),
+        owner: Some(project.owner),
+        archived: project.archived_at.is_some(),
+    }
+}
+
+fn diff(before: &ProjectSnapshot, after: &ProjectSnapshot) -> Vec<RevisionChange> {
+    let mut changes = Vec::new();
+
+    let mut field = |name: &str, before: Option<String>, after: Option<String>| {
+        if before != after {
+            changes.push(RevisionChange::Field { name: name.to_string(), before, after });
+        }
+    };
+
+    let text = |value: &str| (!value.is_empty()).then(|| value.to_string());
+    let list = |values: &[String]| text(&values.join(", "));
+
+    field("name", text(&before.name), text(&after.name));
+    field("description", before.description.clone(), after.description.clone());
+    field(
+        "visibility",
+        text(&format!("{:?}", before.visibility)),
+        text(&format!("{:?}", after.visibility)),
+    );
+    field("genres", list(&before.genres), list(&after.genres));
+    field("moods", list(&before.moods), list(&after.moods));
+    field("tags", list(&before.tags), list(&after.tags));
+    // Snapshots from before the owner was versioned don't know it
+    if let (Some(before), Some(after)) = (before.owner, after.owner) {
+        field("owner", Some(before.to_text()), Some(after.to_text()));
+    }
+    field("archived", text(&before.archived.to_string()), text(&after.archived.to_string()));
+
+    for id in after.tracks.iter().filter(|id| !before.tracks.contains(id)) {
+        changes.push(RevisionChange::TrackAdded(id.clone()));
+    }
+    for id in before.tracks.iter().filter(|id| !after.tracks.contains(id)) {
+        changes.push(RevisionChange::TrackRemoved(id.clone()));
+    }
+
+    // Same tracks in a different order
+    let kept_before: Vec<_> = before.tracks.iter().filter(|id| after.tracks.contains(id)).collect();
+    let kept_after: Vec<_> = after.tracks.iter().filter(|id| before.tracks.contains(id)).collect();
+    if kept_before != kept_after {
+        changes.push(RevisionChange::TracksReordered);
+    }
+
+    for principal in after.collaborators.iter().filter(|p| !before.collaborators.contains(p)) {
+        changes.push(RevisionChange::CollaboratorAdded(principal.clone()));
+    }
+    for principal in before.collaborators.iter().filter(|p| !after.collaborators.contains(p)) {
+        changes.push(RevisionChange::CollaboratorRemoved(principal.clone()));
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::{BoundedStorable, Storable};
+    use super::*;
+    use crate::types::ProjectVisibility;
+
+    fn revision(number: u64, label: Option<&str>, snapshot: ProjectSnapshot) -> ProjectRevision {
+        ProjectRevision {
+            project_id: "project".to_string(),
+            number,
+            author: Principal::from_slice(&[1]),
+            created_at: number,
+            label: label.map(str::to_string),
+            changes: vec![],
+            snapshot,
+        }
+    }
+
+    #[test]
+    fn changes_are_derived_from_the_previous_snapshot() {
+        let first = ProjectSnapshot { name: "Demo".to_string(), ..Default::default() };
+        let second = ProjectSnapshot { name: "Album".to_string(), ..first.clone() };
+
+        save_project_revision(revision(1, None, first));
+        save_project_revision(revision(2, Some("pre-master"), second.clone()));
+        save_project_revision(revision(3, None, second));
+
+        let changes = |number| with_changes(get_project_revision("project", number).unwrap()).changes;
+        assert_eq!(changes(1), vec![]);
+        assert_eq!(changes(2), vec![]);
+        assert_eq!(changes(3), vec![]);
+
+        let renamed = ProjectSnapshot { name: "Album".to_string(), ..Default::default() };
+        let private = ProjectSnapshot { visibility: ProjectVisibility::Private, ..renamed };
+        save_project_revision(revision(4, None, private));
+
+        assert_eq!(
+            changes(4),
+            vec![RevisionChange::Field {
+                name: "visibility".to_string(),
+                before: Some("Public".to_string()),
+                after: Some("Private".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn changes_are_not_stored() {
+        let mut stored = revision(2, None, ProjectSnapshot::default());
+        stored.changes = vec![RevisionChange::TracksReordered];
+
+        let bytes = stored.to_bytes();
+        assert!(ProjectRevision::from_bytes(bytes).changes.is_empty());
+    }
+
+    #[test]
+    fn a_revision_of_the_largest_project_fits_its_bound() {
+        // Emoji take four bytes, and labels may use four-byte letters such as 𠀀
+        let labels = vec!["𠀀".repeat(32); 10];
+        let snapshot = ProjectSnapshot {
+            name: "🎸".repeat(64),
+            description: Some("🎸".repeat(1000)),
+            visibility: ProjectVisibility::CollaboratorsOnly,
+            genres: labels.clone(),
+            moods: labels.clone(),
+            tags: labels,
+            tracks: vec![uuid::Uuid::nil().to_string(); 50],
+            collaborators: vec![Principal::from_slice(&[255; 29]).to_text(); 20],
+            owner: Some(Principal::from_slice(&[255; 29])),
+            archived: true,
+        };
+
+        let mut revision = revision(u64::MAX, Some(&"🎸".repeat(64)), snapshot);
+        revision.author = Principal::from_slice(&[255; 29]);
+
+        let size = revision.to_bytes().len();
+        assert!(size <= ProjectRevision::MAX_SIZE as usize, "{} bytes", size);
+    }
+}
@@ -1,11 +1,12 @@
 use candid::{CandidType, Deserialize, Principal};
 use ic_cdk::api::time;
-use ic_cdk::{caller, id, query, update};
+use ic_cdk::{caller, id, init, post_upgrade, query, update};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
 use ic_stable_structures::{BoundedStorable, DefaultMemoryImpl, StableBTreeMap, Storable};
 use serde::Serialize;
 use std::borrow::Cow;
 use std::cell::RefCell;
+use std::time::Duration;
 use uuid::Uuid;
 
 mod types;
@@ -14,6 +15,22 @@ mod auth;
 mod projects;
 mod nfts;
 mod collaborations;
+mod validation;
+mod admin;
+mod linking;
+mod delegation;
+mod account;
+mod email;
+mod social;
+mod ownership;
+mod pagination;
+mod migrations;
+mod search;
+mod history;
+mod lineage;
+mod activity;
+mod tracks;
+mod random;
 
 use types::*;
 use storage::*;
@@ -24,19 +41,22 @@ thread_local! {
     static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
         RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
 
-    static USERS: RefCell<StableBTreeMap<Principal, User, Memory>> = RefCell::new(
+    // Users stored before their bound grew; drained into USERS by migration 6
+    static LEGACY_USERS: RefCell<StableBTreeMap<Principal, Legacy<User, 1024>, Memory>> = RefCell::new(
         StableBTreeMap::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0))),
         )
     );
 
-    static PROJECTS: RefCell<StableBTreeMap<String, Project, Memory>> = RefCell::new(
+    // Projects stored before their bound grew; drained into PROJECTS by migration 7
+    static LEGACY_PROJECTS: RefCell<StableBTreeMap<String, Legacy<Project, 2048>, Memory>> = RefCell::new(
         StableBTreeMap::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1))),
         )
     );
 
-    static NFTS: RefCell<StableBTreeMap<String, NFT, Memory>> = RefCell::new(
+    // NFTs stored before their bound grew; drained into NFTS by migration 8
+    static LEGACY_NFTS: RefCell<StableBTreeMap<String, Legacy<NFT, 2048>, Memory>> = RefCell::new(
         StableBTreeMap::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2))),
         )
@@ -53,6 +73,323 @@ thread_local! {
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4))),
         )
     );
+
+    // Lowercased handle -> owning principal
+    static HANDLES: RefCell<StableBTreeMap<String, Principal, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5))),
+        )
+    );
+
+    static ROLES: RefCell<StableBTreeMap<Principal, Role, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6))),
+        )
+    );
+
+    static SUSPENSIONS: RefCell<StableBTreeMap<Principal, Suspension, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7))),
+        )
+    );
+
+    // Linked device principal -> canonical account
+    static LINKED_PRINCIPALS: RefCell<StableBTreeMap<Principal, LinkedPrincipal, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8))),
+        )
+    );
+
+    static LINK_CHALLENGES: RefCell<StableBTreeMap<String, LinkChallenge, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(9))),
+        )
+    );
+
+    // Delegate principal -> delegation granted by an account
+    static DELEGATIONS: RefCell<StableBTreeMap<Principal, Delegation, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10))),
+        )
+    );
+
+    static EMAIL_VERIFICATIONS: RefCell<StableBTreeMap<Principal, EmailVerification, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11))),
+        )
+    );
+
+    static OUTBOX: RefCell<StableBTreeMap<u64, OutboundEmail, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12))),
+        )
+    );
+
+    // (follower, followee) -> followed_at
+    static FOLLOWING: RefCell<StableBTreeMap<(Principal, Principal), u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13))),
+        )
+    );
+
+    // (followee, follower) -> followed_at
+    static FOLLOWERS: RefCell<StableBTreeMap<(Principal, Principal), u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(14))),
+        )
+    );
+
+    // Project id -> pending ownership transfer
+    static OWNERSHIP_TRANSFERS: RefCell<StableBTreeMap<String, OwnershipTransfer, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(15))),
+        )
+    );
+
+    // Sort indexes: "{timestamp:020}:{id}" -> ()
+    static PROJECTS_BY_CREATED: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(16))),
+        )
+    );
+
+    static PROJECTS_BY_UPDATED: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(17))),
+        )
+    );
+
+    static NFTS_BY_CREATED: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(18))),
+        )
+    );
+
+    static NFTS_BY_UPDATED: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(19))),
+        )
+    );
+
+    // Canister metadata such as the stored schema version
+    static META: RefCell<StableBTreeMap<String, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(20))),
+        )
+    );
+
+    // Composite "parent:child" keys, so per-parent lookups are range scans
+    static TRACKS_BY_PROJECT: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(21))),
+        )
+    );
+
+    static NFTS_BY_PROJECT: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(22))),
+        )
+    );
+
+    static COLLABORATIONS_BY_PROJECT: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(23))),
+        )
+    );
+
+    static PROJECTS_BY_OWNER: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(24))),
+        )
+    );
+
+    static NFTS_BY_CREATOR: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(25))),
+        )
+    );
+
+    // Inverted index: "token:kind:id" -> term weight in that document
+    static SEARCH_INDEX: RefCell<StableBTreeMap<String, u32, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(26))),
+        )
+    );
+
+    // "project_id:{number:020}" -> revision
+    static PROJECT_REVISIONS: RefCell<StableBTreeMap<String, ProjectRevision, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(27))),
+        )
+    );
+
+    // "parent_project_id:fork_id" -> ()
+    static FORKS: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(28))),
+        )
+    );
+
+    // "project_id:{event_id:020}" -> event
+    static ACTIVITY: RefCell<StableBTreeMap<String, ActivityEvent, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(29))),
+        )
+    );
+
+    // "user_principal:collaboration_id" -> ()
+    static COLLABORATIONS_BY_USER: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(30))),
+        )
+    );
+
+    static INVITATIONS: RefCell<StableBTreeMap<String, Invitation, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(31))),
+        )
+    );
+
+    // "invitee_principal:invitation_id" -> ()
+    static INVITATIONS_BY_INVITEE: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(32))),
+        )
+    );
+
+    // "project_id:invitation_id" -> ()
+    static INVITATIONS_BY_PROJECT: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(33))),
+        )
+    );
+
+    // "track_id:{sequence:020}" -> status change
+    static TRACK_STATUS_HISTORY: RefCell<StableBTreeMap<String, TrackStatusChange, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(34))),
+        )
+    );
+
+    // "track_id:{number:020}" -> version
+    static TRACK_VERSIONS: RefCell<StableBTreeMap<String, TrackVersion, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(35))),
+        )
+    );
+
+    // "track_id:stem_id" -> stem
+    static STEMS: RefCell<StableBTreeMap<String, Stem, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(36))),
+        )
+    );
+
+    static USERS: RefCell<StableBTreeMap<Principal, User, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(37))),
+        )
+    );
+
+    static PROJECTS: RefCell<StableBTreeMap<String, Project, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(38))),
+        )
+    );
+
+    // Track id -> lyrics and credits
+    static TRACK_DETAILS: RefCell<StableBTreeMap<String, TrackDetails, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(39))),
+        )
+    );
+
+    // "nft_id:track_id" -> pinned version number
+    static NFT_PINS: RefCell<StableBTreeMap<String, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(40))),
+        )
+    );
+
+    // Principals allowed to drain the email outbox -> added_at
+    static MAILERS: RefCell<StableBTreeMap<Principal, u64, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(41))),
+        )
+    );
+
+    static NFTS: RefCell<StableBTreeMap<String, NFT, Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(42))),
+        )
+    );
+
+    // "uploader_principal:track_id:{number:020}" -> ()
+    static TRACK_VERSIONS_BY_UPLOADER: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(43))),
+        )
+    );
+
+    // "contributor_principal:track_id:stem_id" -> ()
+    static STEMS_BY_CONTRIBUTOR: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(44))),
+        )
+    );
+
+    // "party_principal:project_id" -> (), once for the sender and once for the recipient
+    static OWNERSHIP_TRANSFERS_BY_PARTY: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(45))),
+        )
+    );
+
+    // "account_principal:code" -> ()
+    static LINK_CHALLENGES_BY_ACCOUNT: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(46))),
+        )
+    );
+
+    // "lowercased_recipient:{id:020}" -> ()
+    static OUTBOX_BY_RECIPIENT: RefCell<StableBTreeMap<String, (), Memory>> = RefCell::new(
+        StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(47))),
+        )
+    );
+}
+
+const SUSPENSION_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// Timers don't survive upgrades, so this runs from both init and post_upgrade
+fn start_timers() {
+    ic_cdk_timers::set_timer_interval(SUSPENSION_SWEEP_INTERVAL, admin::lift_expired_suspensions);
+    collaborations::schedule_invitation_expiries();
+    random::schedule_seeding();
+}
+
+// Lifecycle
+#[init]
+fn init(args: Option<InitArgs>) {
+    let args = args.unwrap_or_default();
+
+    // Fall back to the installing principal so a fresh canister always has an admin
+    if args.admins.is_empty() {
+        admin::seed_admins(vec![caller()]);
+    } else {
+        admin::seed_admins(args.admins);
+    }
+
+    storage::set_schema_version(migrations::CURRENT_SCHEMA_VERSION);
+    start_timers();
+}
+
+#[post_upgrade]
+fn post_upgrade(args: Option<InitArgs>) {
+    migrations::run_migrations();
+    admin::seed_admins(args.unwrap_or_default().admins);
+    start_timers();
 }
 
 // Authentication
@@ -66,10 +403,203 @@ fn get_user() -> Result<User, String> {
     auth::get_user()
 }
 
+#[update]
+fn update_user(
+    name: Option<String>,
+    email: Option<String>,
+    avatar_url: Option<String>,
+    bio: Option<String>,
+    links: Option<Vec<String>>,
+    location: Option<String>,
+) -> Result<User, String> {
+    auth::update_user(name, email, avatar_url, bio, links, location)
+}
+
+#[update]
+fn set_handle(handle: String) -> Result<User, String> {
+    auth::set_handle(handle)
+}
+
+#[query]
+fn get_user_by_handle(handle: String) -> Result<Principal, String> {
+    auth::get_user_by_handle(handle)
+}
+
+#[query]
+fn get_public_profile(handle: String) -> Result<PublicProfile, String> {
+    auth::get_public_profile(handle)
+}
+
+#[query]
+fn get_profile(principal: Principal) -> Result<PublicProfile, String> {
+    auth::get_profile(principal)
+}
+
+#[update]
+fn update_privacy_settings(settings: PrivacySettings) -> Result<User, String> {
+    auth::update_privacy_settings(settings)
+}
+
+// Social graph
+#[update]
+fn follow_user(principal: Principal) -> Result<(), String> {
+    social::follow_user(principal)
+}
+
+#[update]
+fn unfollow_user(principal: Principal) -> Result<(), String> {
+    social::unfollow_user(principal)
+}
+
+#[query]
+fn get_followers(principal: Principal, start_after: Option<Principal>, limit: Option<u32>) -> FollowPage {
+    social::get_followers(principal, start_after, limit)
+}
+
+#[query]
+fn get_following(principal: Principal, start_after: Option<Principal>, limit: Option<u32>) -> FollowPage {
+    social::get_following(principal, start_after, limit)
+}
+
+#[query]
+fn is_following(follower: Principal, followee: Principal) -> bool {
+    social::is_following_user(follower, followee)
+}
+
+// Email verification
+#[update]
+fn request_email_verification() -> Result<User, String> {
+    email::request_email_verification()
+}
+
+#[update]
+fn verify_email(code: String) -> Result<User, String> {
+    email::verify_email(code)
+}
+
+#[query]
+fn get_pending_emails(limit: u32) -> Result<Vec<OutboundEmail>, String> {
+    email::get_pending_emails(limit)
+}
+
+#[update]
+fn ack_emails(ids: Vec<u64>) -> Result<(), String> {
+    email::ack_emails(ids)
+}
+
+#[update]
+fn add_mailer(principal: Principal) -> Result<(), String> {
+    email::add_mailer(principal)
+}
+
+#[update]
+fn remove_mailer(principal: Principal) -> Result<(), String> {
+    email::remove_mailer(principal)
+}
+
+#[query]
+fn list_mailers() -> Result<Vec<Principal>, String> {
+    email::list_mailers()
+}
+
+// Account data
+#[query]
+fn export_my_data() -> Result<UserDataExport, String> {
+    account::export_my_data()
+}
+
+#[update]
+fn delete_account(disposition: ProjectDisposition) -> Result<(), String> {
+    account::delete_account(disposition)
+}
+
+// Linked principals
+#[update]
+fn create_link_challenge() -> Result<LinkChallenge, String> {
+    linking::create_link_challenge()
+}
+
+#[update]
+fn redeem_link_challenge(code: String) -> Result<LinkedPrincipal, String> {
+    linking::redeem_link_challenge(code)
+}
+
+#[update]
+fn unlink_principal(principal: Principal) -> Result<(), String> {
+    linking::unlink_principal(principal)
+}
+
+#[query]
+fn list_linked_principals() -> Result<Vec<LinkedPrincipal>, String> {
+    linking::list_linked_principals()
+}
+
+// Delegations
+#[update]
+fn authorize_delegate(delegate: Principal, scopes: Vec<Scope>, duration_seconds: u64) -> Result<Delegation, String> {
+    delegation::authorize_delegate(delegate, scopes, duration_seconds)
+}
+
+#[update]
+fn revoke_delegate(delegate: Principal) -> Result<Delegation, String> {
+    delegation::revoke_delegate(delegate)
+}
+
+#[query]
+fn list_delegates() -> Result<Vec<Delegation>, String> {
+    delegation::list_delegates()
+}
+
+// Roles
+#[update]
+fn set_user_role(principal: Principal, role: Role) -> Result<RoleAssignment, String> {
+    admin::set_user_role(principal, role)
+}
+
+#[query]
+fn get_user_role(principal: Principal) -> Role {
+    admin::get_user_role(principal)
+}
+
+#[query]
+fn list_role_assignments() -> Result<Vec<RoleAssignment>, String> {
+    admin::list_role_assignments()
+}
+
+// Moderation
+#[update]
+fn suspend_user(principal: Principal, reason: String, duration_seconds: u64) -> Result<Suspension, String> {
+    admin::suspend_user(principal, reason, duration_seconds)
+}
+
+#[update]
+fn ban_user(principal: Principal, reason: String) -> Result<Suspension, String> {
+    admin::ban_user(principal, reason)
+}
+
+#[update]
+fn unsuspend_user(principal: Principal) -> Result<(), String> {
+    admin::unsuspend_user(principal)
+}
+
+#[query]
+fn get_suspension(principal: Principal) -> Result<Option<Suspension>, String> {
+    admin::get_suspension(principal)
+}
+
+#[query]
+fn list_suspensions() -> Result<Vec<Suspension>, String> {
+    admin::list_suspensions()
+}
+
 // Projects
 #[update]
-fn create_project(name: String, description: Option<String>) -> Result<Project, String> {
-    projects::create_project(name, description)
+fn create_project(
+    name: String,
+    description: Option<String>,
+    visibility: Option<ProjectVisibility>,
+) -> Result<Project, String> {
+    projects::create_project(name, description, visibility)
 }
 
 #[query]
@@ -77,14 +607,138 @@ fn get_projects() -> Vec<Project> {
     projects::get_projects()
 }
 
+#[query]
+fn list_projects(query: ProjectQuery) -> Result<ProjectPage, String> {
+    projects::list_projects(query)
+}
+
 #[query]
 fn get_project(id: String) -> Result<Project, String> {
     projects::get_project(id)
 }
 
 #[update]
-fn update_project(id: String, name: Option<String>, description: Option<String>) -> Result<Project, String> {
-    projects::update_project(id, name, description)
+fn update_project(
+    id: String,
+    name: Option<String>,
+    description: Option<String>,
+    visibility: Option<ProjectVisibility>,
+) -> Result<Project, String> {
+    projects::update_project(id, name, description, visibility)
+}
+
+#[query]
+fn get_archived_projects() -> Result<Vec<Project>, String> {
+    projects::get_archived_projects()
+}
+
+#[update]
+fn archive_project(id: String) -> Result<Project, String> {
+    projects::archive_project(id)
+}
+
+#[update]
+fn restore_project(id: String) -> Result<Project, String> {
+    projects::restore_project(id)
+}
+
+#[update]
+fn delete_project(id: String) -> Result<(), String> {
+    projects::delete_project(id)
+}
+
+#[update]
+fn set_project_tags(
+    id: String,
+    genres: Vec<String>,
+    moods: Vec<String>,
+    tags: Vec<String>,
+) -> Result<Project, String> {
+    projects::set_project_tags(id, genres, moods, tags)
+}
+
+// Remixes
+#[update]
+fn fork_project(project_id: String, name: Option<String>) -> Result<Project, String> {
+    lineage::fork_project(project_id, name)
+}
+
+#[update]
+fn set_remix_settings(
+    project_id: String,
+    allow_remixes: bool,
+    upstream_royalty_percentage: u8,
+) -> Result<Project, String> {
+    lineage::set_remix_settings(project_id, allow_remixes, upstream_royalty_percentage)
+}
+
+#[query]
+fn get_project_lineage(project_id: String) -> Result<ProjectLineage, String> {
+    lineage::get_project_lineage(project_id)
+}
+
+// Activity
+#[query]
+fn get_project_activity(project_id: String, page: PageRequest) -> Result<ActivityPage, String> {
+    activity::get_project_activity(project_id, page)
+}
+
+#[query]
+fn get_activity_feed(page: PageRequest) -> Result<ActivityPage, String> {
+    activity::get_activity_feed(page)
+}
+
+// Project history
+#[query]
+fn get_project_history(project_id: String, page: PageRequest) -> Result<RevisionPage, String> {
+    history::get_project_history(project_id, page)
+}
+
+#[update]
+fn create_project_snapshot(project_id: String, label: String) -> Result<ProjectRevision, String> {
+    history::create_project_snapshot(project_id, label)
+}
+
+#[update]
+fn restore_project_revision(project_id: String, number: u64) -> Result<Project, String> {
+    history::restore_project_revision(project_id, number)
+}
+
+// Search
+#[query]
+fn search(query: SearchQuery) -> Result<SearchPage, String> {
+    search::search(query)
+}
+
+// Ownership transfers
+#[update]
+fn propose_ownership_transfer(
+    project_id: String,
+    to: Principal,
+    require_collaborator_approval: bool,
+    duration_seconds: Option<u64>,
+) -> Result<OwnershipTransfer, String> {
+    ownership::propose_ownership_transfer(project_id, to, require_collaborator_approval, duration_seconds)
+}
+
+#[update]
+fn approve_ownership_transfer(project_id: String) -> Result<OwnershipTransfer, String> {
+    ownership::approve_ownership_transfer(project_id)
+}
+
+#[update]
+fn accept_ownership_transfer(project_id: String) -> Result<Project, String> {
+    ownership::accept_ownership_transfer(project_id)
+}
+
+#[update]
+fn cancel_transfer(project_id: String) -> Result<(), String> {
+    ownership::cancel_transfer(project_id)
+}
+
+#[query]
+fn get_ownership_transfer(project_id: String) -> Option<OwnershipTransfer> {
+    ownership::get_ownership_transfer(project_id)
 }
 
 // Tracks
@@ -98,6 +752,118 @@ fn get_project_tracks(project_id: String) -> Vec<Track> {
     projects::get_project_tracks(project_id)
 }
 
+#[query]
+fn list_project_tracks(project_id: String, query: TrackQuery) -> Result<TrackPage, String> {
+    projects::list_project_tracks(project_id, query)
+}
+
+#[update]
+fn update_track(
+    track_id: String,
+    name: Option<String>,
+    ipfs_hash: Option<String>,
+    duration: Option<u64>,
+) -> Result<Track, String> {
+    tracks::update_track(track_id, name, ipfs_hash, duration)
+}
+
+#[update]
+fn update_track_metadata(track_id: String, metadata: TrackMetadata) -> Result<Track, String> {
+    tracks::update_track_metadata(track_id, metadata)
+}
+
+#[update]
+fn update_track_details(
+    track_id: String,
+    lyrics: Option<String>,
+    credits: Vec<TrackCredit>,
+) -> Result<TrackDetails, String> {
+    tracks::update_track_details(track_id, lyrics, credits)
+}
+
+#[query]
+fn get_track_details(track_id: String) -> Result<TrackDetails, String> {
+    tracks::get_track_details(track_id)
+}
+
+#[update]
+fn delete_track(track_id: String) -> Result<(), String> {
+    tracks::delete_track(track_id)
+}
+
+#[update]
+fn reorder_tracks(project_id: String, track_ids: Vec<String>) -> Result<Project, String> {
+    tracks::reorder_tracks(project_id, track_ids)
+}
+
+#[update]
+fn update_track_status(track_id: String, status: TrackStatus) -> Result<Track, String> {
+    tracks::update_track_status(track_id, status)
+}
+
+#[update]
+fn add_track_version(
+    track_id: String,
+    ipfs_hash: String,
+    duration: u64,
+    notes: Option<String>,
+    activate: bool,
+) -> Result<TrackVersion, String> {
+    tracks::add_track_version(track_id, ipfs_hash, duration, notes, activate)
+}
+
+#[update]
+fn set_active_track_version(track_id: String, version: u64) -> Result<Track, String> {
+    tracks::set_active_track_version(track_id, version)
+}
+
+#[query]
+fn get_track_versions(track_id: String) -> Result<Vec<TrackVersion>, String> {
+    tracks::get_track_versions(track_id)
+}
+
+#[query]
+fn compare_track_versions(track_id: String, from: u64, to: u64) -> Result<TrackVersionComparison, String> {
+    tracks::compare_track_versions(track_id, from, to)
+}
+
+#[update]
+fn add_stem(
+    track_id: String,
+    role: StemRole,
+    ipfs_hash: String,
+    sample_rate: u32,
+    channels: u8,
+) -> Result<Stem, String> {
+    tracks::add_stem(track_id, role, ipfs_hash, sample_rate, channels)
+}
+
+#[update]
+fn replace_stem(
+    track_id: String,
+    stem_id: String,
+    ipfs_hash: String,
+    sample_rate: u32,
+    channels: u8,
+) -> Result<Stem, String> {
+    tracks::replace_stem(track_id, stem_id, ipfs_hash, sample_rate, channels)
+}
+
+#[update]
+fn remove_stem(track_id: String, stem_id: String) -> Result<(), String> {
+    tracks::remove_stem(track_id, stem_id)
+}
+
+#[query]
+fn get_track_stems(track_id: String) -> Result<Vec<Stem>, String> {
+    tracks::get_track_stems(track_id)
+}
+
+#[query]
+fn get_track_status_history(track_id: String) -> Result<Vec<TrackStatusChange>, String> {
+    tracks::get_track_status_history(track_id)
+}
+
 // NFTs
 #[update]
 fn create_nft(
@@ -116,6 +882,11 @@ fn get_nfts() -> Vec<NFT> {
     nfts::get_nfts()
 }
 
+#[query]
+fn list_nfts(query: NftQuery) -> Result<NftPage, String> {
+    nfts::list_nfts(query)
+}
+
 #[query]
 fn get_nft(id: String) -> Result<NFT, String> {
     nfts::get_nft(id)
@@ -126,6 +897,16 @@ fn get_project_nfts(project_id: String) -> Vec<NFT> {
     nfts::get_project_nfts(project_id)
 }
 
+#[update]
+fn pin_track_version(nft_id: String, track_id: String, version: u64) -> Result<Vec<PinnedTrackVersion>, String> {
+    nfts::pin_track_version(nft_id, track_id, version)
+}
+
+#[query]
+fn get_nft_pins(nft_id: String) -> Result<Vec<PinnedTrackVersion>, String> {
+    nfts::get_nft_pins(nft_id)
+}
+
 #[update]
 fn mint_nft(id: String, token_id: String, contract_address: String) -> Result<NFT, String> {
     nfts::mint_nft(id, token_id, contract_address)
@@ -133,13 +914,39 @@ fn mint_nft(id: String, token_id: String, contract_address: String) -> Result<NF
 
 // Collaborations
 #[update]
-fn add_collaborator(
+fn invite_collaborator(
     project_id: String,
-    user_principal: Principal,
+    invitee: Principal,
     contribution_percentage: u8,
     role: String,
-) -> Result<Collaboration, String> {
-    collaborations::add_collaborator(project_id, user_principal, contribution_percentage, role)
+    duration_seconds: Option<u64>,
+) -> Result<Invitation, String> {
+    collaborations::invite_collaborator(project_id, invitee, contribution_percentage, role, duration_seconds)
+}
+
+#[update]
+fn accept_invitation(id: String) -> Result<Collaboration, String> {
+    collaborations::accept_invitation(id)
+}
+
+#[update]
+fn decline_invitation(id: String) -> Result<Invitation, String> {
+    collaborations::decline_invitation(id)
+}
+
+#[update]
+fn revoke_invitation(id: String) -> Result<Invitation, String> {
+    collaborations::revoke_invitation(id)
+}
+
+#[query]
+fn get_my_invitations() -> Result<Vec<Invitation>, String> {
+    collaborations::get_my_invitations()
+}
+
+#[query]
+fn get_project_invitations(project_id: String) -> Result<Vec<Invitation>, String> {
+    collaborations::get_project_invitations(project_id)
 }
 
 #[query]
@@ -147,6 +954,11 @@ fn get_project_collaborators(project_id: String) -> Vec<Collaboration> {
     collaborations::get_project_collaborators(project_id)
 }
 
+#[query]
+fn list_project_collaborators(project_id: String, page: PageRequest) -> Result<CollaborationPage, String> {
+    collaborations::list_project_collaborators(project_id, page)
+}
+
 #[update]
 fn remove_collaborator(project_id: String, collaboration_id: String) -> Result<(), String> {
     collaborations::remove_collaborator(project_id, collaboration_id)
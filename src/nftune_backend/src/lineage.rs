@@ -0,0 +1,257 @@
+use std::collections::VecDeque;
+use candid::Principal;
+use ic_cdk::api::time;
+use uuid::Uuid;
+use crate::types::{
+    ActivityKind, LineageEntry, Project, ProjectLineage, ProjectVisibility, RoyaltyShare, Stem, Track,
+    TrackDetails, TrackMetadata, TrackStatus,
+};
+use crate::storage::{
+    get_project_by_id, get_tracks_by_project, get_fork_ids, save_track, get_track_stems, save_stem,
+    get_track_details, save_track_details,
+};
+use crate::auth::{require_active, caller_principal};
+use crate::history::save_project_with_history;
+use crate::activity;
+use crate::validation;
+use crate::tracks::record_initial_version;
+use crate::projects::{can_view_project, ensure_not_archived, get_visible_project, is_project_owner};
+
+/// Most a single project can ask of its forks.
+const MAX_UPSTREAM_ROYALTY_PERCENTAGE: u8 = 25;
+
+/// Upstream shares plus the NFT creator's own royalty can't exceed this.
+pub const MAX_TOTAL_ROYALTY_PERCENTAGE: u8 = 50;
+
+/// Keeps `upstream_royalties` within the project and NFT storage bounds.
+pub const MAX_UPSTREAM_SHARES: usize = 4;
+
+/// Bounds lineage walks so deep or wide fork trees can't exhaust the
+/// instruction limit.
+const MAX_LINEAGE_DEPTH: u32 = 32;
+const MAX_DESCENDANTS: usize = 500;
+
+/// Starts a new private project from `project_id`, copying its metadata and
+/// its tracks' current audio, stems, lyrics and credits. Earlier track
+/// versions, status history and the parent's revision history are not
+/// carried over. Other users can only fork projects that allow remixes.
+pub fn fork_project(project_id: String, name: Option<String>) -> Result<Project, String> {
+    let caller = require_active()?;
+
+    let parent = get_visible_project(&project_id, caller)
+        .ok_or_else(|| "Project not found".to_string())?;
+
+    if !parent.allow_remixes && !is_project_owner(&parent, caller) {
+        return Err("This project does not allow remixes".to_string());
+    }
+
+    ensure_not_archived(&parent)?;
+
+    let name = name.unwrap_or_else(|| format!("{} (remix)", parent.name));
+    validation::validate_name(&name)?;
+
+    let upstream_royalties = inherited_royalties(&parent);
+    if upstream_royalties.len() > MAX_UPSTREAM_SHARES {
+        return Err(format!(
+            "A remix can owe royalty shares to at most {} ancestors",
+            MAX_UPSTREAM_SHARES
+        ));
+    }
+
+    let upstream_total: u32 = upstream_royalties.iter().map(|share| share.percentage as u32).sum();
+    if upstream_total > MAX_TOTAL_ROYALTY_PERCENTAGE as u32 {
+        return Err(format!(
+            "Upstream royalty shares would exceed {}%",
+            MAX_TOTAL_ROYALTY_PERCENTAGE
+        ));
+    }
+
+    let now = time();
+    let mut fork = Project {
+        id: Uuid::new_v4().to_string(),
+        owner: caller,
+        name,
+        description: parent.description.clone(),
+        collaborators: vec![],
+        tracks: vec![],
+        nfts: vec![],
+        visibility: ProjectVisibility::Private,
+        genres: parent.genres.clone(),
+        moods: parent.moods.clone(),
+        tags: parent.tags.clone(),
+        allow_remixes: false,
+        upstream_royalty_percentage: 0,
+        parent_project_id: Some(parent.id.clone()),
+        upstream_royalties,
+        archived_at: None,
+        created_at: now,
+        updated_at: now,
+    };
+
+    // New track records pointing at the same audio, in the parent's order
+    let mut tracks = get_tracks_by_project(&parent.id);
+    tracks.sort_by_key(|track| parent.tracks.iter().position(|id| id == &track.id));
+
+    for track in tracks {
+        let mut copy = Track {
+            id: Uuid::new_v4().to_string(),
+            project_id: fork.id.clone(),
+            name: track.name,
+            ipfs_hash: track.ipfs_hash,
+            duration: track.duration,
+            status: TrackStatus::Draft,
+            delegate: None,
+            active_version: None,
+            // An ISRC identifies one recording, so the remix needs its own
+            metadata: TrackMetadata { isrc: None, ..track.metadata },
+            created_at: now,
+        };
+        record_initial_version(&mut copy, caller);
+
+        for stem in get_track_stems(&track.id) {
+            save_stem(Stem {
+                id: Uuid::new_v4().to_string(),
+                track_id: copy.id.clone(),
+                created_at: now,
+                updated_at: now,
+                ..stem
+            });
+        }
+
+        if let Some(details) = get_track_details(&track.id) {
+            save_track_details(TrackDetails { track_id: copy.id.clone(), updated_at: now, ..details });
+        }
+
+        fork.tracks.push(copy.id.clone());
+        save_track(copy);
+    }
+
+    save_project_with_history(fork.clone(), caller);
+    activity::record(&fork.id, caller, ActivityKind::ProjectCreated);
+    Ok(fork)
+}
+
+/// Opens a project to remixes and sets the royalty share its forks owe it.
+/// Existing forks keep the share that applied when they were made.
+pub fn set_remix_settings(
+    project_id: String,
+    allow_remixes: bool,
+    upstream_royalty_percentage: u8,
+) -> Result<Project, String> {
+    let caller = require_active()?;
+
+    let mut project = get_project_by_id(&project_id)
+        .ok_or_else(|| "Project not found".to_string())?;
+
+    if !is_project_owner(&project, caller) {
+        return Err("Only project owner can change remix settings".to_string());
+    }
+
+    ensure_not_archived(&project)?;
+
+    if upstream_royalty_percentage > MAX_UPSTREAM_ROYALTY_PERCENTAGE {
+        return Err(format!(
+            "Upstream royalty percentage cannot exceed {}%",
+            MAX_UPSTREAM_ROYALTY_PERCENTAGE
+        ));
+    }
+
+    project.allow_remixes = allow_remixes;
+    project.upstream_royalty_percentage = upstream_royalty_percentage;
+    project.updated_at = time();
+
+    save_project_with_history(project.clone(), caller);
+    activity::record(&project_id, caller, ActivityKind::ProjectUpdated);
+    Ok(project)
+}
+
+/// The fork tree around a project: its chain of parents and every fork
+/// made from it, directly or indirectly. Projects the caller can't view are
+/// left out, along with everything reached only through them.
+pub fn get_project_lineage(project_id: String) -> Result<ProjectLineage, String> {
+    let caller = caller_principal();
+
+    let project = get_visible_project(&project_id, caller)
+        .ok_or_else(|| "Project not found".to_string())?;
+
+    let mut ancestors = Vec::new();
+    let mut parent_id = project.parent_project_id.clone();
+
+    while let Some(id) = parent_id {
+        if ancestors.len() as u32 >= MAX_LINEAGE_DEPTH {
+            break;
+        }
+
+        // A deleted or hidden ancestor ends the chain
+        let Some(parent) = get_project_by_id(&id).filter(|parent| can_view_project(parent, caller)) else {
+            break;
+        };
+
+        parent_id = parent.parent_project_id.clone();
+        ancestors.push(lineage_entry(&parent, ancestors.len() as u32 + 1, caller));
+    }
+
+    let mut descendants = Vec::new();
+    let mut queue: VecDeque<(String, u32)> = VecDeque::from([(project.id.clone(), 0)]);
+
+    while let Some((id, depth)) = queue.pop_front() {
+        if depth >= MAX_LINEAGE_DEPTH {
+            continue;
+        }
+
+        for fork_id in get_fork_ids(&id) {
+            if descendants.len() >= MAX_DESCENDANTS {
+                break;
+            }
+
+            let fork = get_project_by_id(&fork_id).filter(|fork| can_view_project(fork, caller));
+
+            if let Some(fork) = fork {
+                descendants.push(lineage_entry(&fork, depth + 1, caller));
+                queue.push_back((fork_id, depth + 1));
+            }
+        }
+    }
+
+    Ok(ProjectLineage { ancestors, descendants })
+}
+
+/// Current recipients of a project's upstream shares. Shares follow the
+/// ancestor's current owner; deleted ancestors keep the last known one.
+pub fn resolve_upstream_royalties(project: &Project) -> Vec<RoyaltyShare> {
+    project
+        .upstream_royalties
+        .iter()
+        .map(|share| RoyaltyShare {
+            recipient: get_project_by_id(&share.project_id)
+                .map_or(share.recipient, |ancestor| ancestor.owner),
+            ..share.clone()
+        })
+        .collect()
+}
+
+/// The parent's own upstream shares plus whatever it asks of its forks.
+fn inherited_royalties(parent: &Project) -> Vec<RoyaltyShare> {
+    let mut shares = parent.upstream_royalties.clone();
+
+    if parent.upstream_royalty_percentage > 0 {
+        shares.push(RoyaltyShare {
+            project_id: parent.id.clone(),
+            recipient: parent.owner,
+            percentage: parent.upstream_royalty_percentage,
+        });
+    }
+
+    shares
+}
+
+fn lineage_entry(project: &Project, depth: u32, caller: Principal) -> LineageEntry {
+    LineageEntry {
+        project_id: project.id.clone(),
+        parent_project_id: project.parent_project_id.clone().filter(|id| {
+            get_project_by_id(id).is_some_and(|parent| can_view_project(&parent, caller))
+        }),
+        name: project.name.clone(),
+        depth,
+    }
+}
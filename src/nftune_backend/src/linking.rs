@@ -0,0 +1,183 @@
+use candid::Principal;
+use ic_cdk::{caller, api::time};
+use crate::types::{LinkChallenge, LinkedPrincipal};
+use crate::storage::{
+    get_user_by_principal, get_linked_principal, get_delegation, save_linked_principal, remove_linked_principal,
+    get_linked_principals_for_account, get_link_challenge, save_link_challenge,
+    remove_link_challenge, get_link_challenges_for_account, get_failed_link_redemptions,
+    record_failed_link_redemption,
+};
+use crate::auth::require_active;
+use crate::random;
+
+const LINK_CHALLENGE_TTL_NS: u64 = 10 * 60 * 1_000_000_000;
+const LINK_CODE_LENGTH: usize = 8;
+// Unambiguous characters only, so codes can be typed from another screen
+const LINK_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+// Codes are looked up by value, so a wrong guess can't be pinned to one
+// account; failures are capped across the canister instead. At this rate
+// guessing any of the few live 40-bit codes stays out of reach, at the cost
+// of pausing redemption for everyone while someone is guessing.
+const MAX_FAILED_REDEMPTIONS: u64 = 100;
+const FAILED_REDEMPTION_WINDOW_NS: u64 = 60 * 60 * 1_000_000_000;
+
+/// Step one: an authenticated device issues a short-lived code that another
+/// principal can redeem to join the caller's account. Issuing a new code
+/// invalidates any earlier code for the same account.
+pub fn create_link_challenge() -> Result<LinkChallenge, String> {
+    let account = require_active()?;
+    let now = time();
+
+    for challenge in get_link_challenges_for_account(account) {
+        remove_link_challenge(&challenge.code);
+    }
+
+    let mut code = generate_code()?;
+    while get_link_challenge(&code).is_some() {
+        code = generate_code()?;
+    }
+
+    let challenge = LinkChallenge {
+        code,
+        account,
+        created_at: now,
+        expires_at: now + LINK_CHALLENGE_TTL_NS,
+    };
+
+    save_link_challenge(challenge.clone());
+    Ok(challenge)
+}
+
+/// Step two: the new principal redeems the code and becomes a linked device.
+pub fn redeem_link_challenge(code: String) -> Result<LinkedPrincipal, String> {
+    let principal = caller();
+
+    if principal == Principal::anonymous() {
+        return Err("Anonymous principals cannot be linked".to_string());
+    }
+
+    let now = time();
+    let challenge = find_link_challenge(&code, now)?;
+
+    if challenge.expires_at <= now {
+        remove_link_challenge(&challenge.code);
+        return Err("Link code has expired".to_string());
+    }
+
+    if principal == challenge.account {
+        return Err("Link code must be redeemed from a different principal".to_string());
+    }
+
+    if get_user_by_principal(principal).is_some() {
+        return Err("Principal already has its own account".to_string());
+    }
+
+    if get_linked_principal(principal).is_some() {
+        return Err("Principal is already linked to an account".to_string());
+    }
+
+    if get_delegation(principal).is_some() {
+        return Err("Delegate principals cannot be linked".to_string());
+    }
+
+    let link = LinkedPrincipal {
+        principal,
+        account: challenge.account,
+        linked_at: now,
+    };
+
+    remove_link_challenge(&challenge.code);
+    save_linked_principal(link.clone());
+    Ok(link)
+}
+
+/// Removes a linked device. Any principal of the account may unlink any of
+/// its devices, including the one making the call.
+pub fn unlink_principal(principal: Principal) -> Result<(), String> {
+    let account = require_active()?;
+
+    let link = get_linked_principal(principal)
+        .ok_or_else(|| "Principal is not linked".to_string())?;
+
+    if link.account != account {
+        return Err("Principal is not linked to your account".to_string());
+    }
+
+    remove_linked_principal(principal);
+    Ok(())
+}
+
+pub fn list_linked_principals() -> Result<Vec<LinkedPrincipal>, String> {
+    let account = require_active()?;
+    Ok(get_linked_principals_for_account(account))
+}
+
+/// Looks up a code, refusing once too many wrong codes were tried recently.
+fn find_link_challenge(code: &str, now: u64) -> Result<LinkChallenge, String> {
+    if get_failed_link_redemptions(now, FAILED_REDEMPTION_WINDOW_NS) >= MAX_FAILED_REDEMPTIONS {
+        return Err("Too many invalid link codes, try again later".to_string());
+    }
+
+    get_link_challenge(&code.trim().to_uppercase()).ok_or_else(|| {
+        record_failed_link_redemption(now, FAILED_REDEMPTION_WINDOW_NS);
+        "Invalid link code".to_string()
+    })
+}
+
+fn generate_code() -> Result<String, String> {
+    // 32 divides 256, so every character is equally likely
+    Ok(random::random_bytes()?
+        .iter()
+        .take(LINK_CODE_LENGTH)
+        .map(|byte| LINK_CODE_ALPHABET[*byte as usize % LINK_CODE_ALPHABET.len()] as char)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn challenge(code: &str) -> LinkChallenge {
+        LinkChallenge {
+            code: code.to_string(),
+            account: Principal::from_slice(&[1]),
+            created_at: 0,
+            expires_at: LINK_CHALLENGE_TTL_NS,
+        }
+    }
+
+    #[test]
+    fn codes_use_the_alphabet_and_differ_between_calls() {
+        random::mix_seed(&[7; 32]);
+
+        let first = generate_code().unwrap();
+        let second = generate_code().unwrap();
+
+        assert_eq!(first.len(), LINK_CODE_LENGTH);
+        assert!(first.bytes().all(|byte| LINK_CODE_ALPHABET.contains(&byte)));
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn codes_match_regardless_of_case_and_padding() {
+        save_link_challenge(challenge("ABCD2345"));
+
+        assert_eq!(find_link_challenge(" abcd2345 ", 0).unwrap().code, "ABCD2345");
+        assert_eq!(get_failed_link_redemptions(0, FAILED_REDEMPTION_WINDOW_NS), 0);
+    }
+
+    #[test]
+    fn redemption_pauses_after_too_many_wrong_codes() {
+        save_link_challenge(challenge("ABCD2345"));
+
+        for _ in 0..MAX_FAILED_REDEMPTIONS {
+            assert_eq!(find_link_challenge("WRONG234", 1).unwrap_err(), "Invalid link code");
+        }
+
+        // Even the right code is refused until the window passes
+        let result = find_link_challenge("ABCD2345", 2);
+        assert_eq!(result.unwrap_err(), "Too many invalid link codes, try again later");
+
+        assert!(find_link_challenge("ABCD2345", 1 + FAILED_REDEMPTION_WINDOW_NS).is_ok());
+    }
+}
@@ -0,0 +1,53 @@
+use crate::storage::{
+    get_schema_version, set_schema_version, rebuild_sort_indexes, rebuild_entity_indexes,
+    rebuild_search_index, backfill_track_versions, move_legacy_users,
+    move_legacy_projects, move_legacy_nfts, rebuild_account_indexes,
+};
+
+/// Bump when stable data needs a one-time backfill, and add a step below.
+pub const CURRENT_SCHEMA_VERSION: u64 = 9;
+
+/// Runs each backfill the stored data hasn't seen yet. Called from
+/// `post_upgrade`; fresh installs start at `CURRENT_SCHEMA_VERSION`.
+pub fn run_migrations() {
+    let version = get_schema_version();
+
+    // Maps moved to a new memory go first, so later steps read the new one
+    if version < 6 {
+        move_legacy_users();
+    }
+
+    if version < 7 {
+        move_legacy_projects();
+    }
+
+    if version < 8 {
+        move_legacy_nfts();
+    }
+
+    if version < 1 {
+        rebuild_sort_indexes();
+    }
+
+    // Versions 2 and 4 both added entity indexes; one rebuild covers both
+    if version < 4 {
+        rebuild_entity_indexes();
+    }
+
+    if version < 3 {
+        rebuild_search_index();
+    }
+
+    if version < 5 {
+        backfill_track_versions();
+    }
+
+    // Runs after the version backfill so backfilled uploads are indexed too
+    if version < 9 {
+        rebuild_account_indexes();
+    }
+
+    if version < CURRENT_SCHEMA_VERSION {
+        set_schema_version(CURRENT_SCHEMA_VERSION);
+    }
+}
@@ -1,8 +1,17 @@
+use candid::Principal;
 use ic_cdk::api::time;
 use uuid::Uuid;
-use crate::types::NFT;
-use crate::storage::{get_nft_by_id, save_nft, get_all_nfts, get_project_by_id};
-use crate::auth::require_authenticated;
+use crate::types::{ActivityKind, PinnedTrackVersion, TrackStatus, NftPage, NftQuery, Scope, SortField, NFT};
+use crate::auth::caller_principal;
+use crate::storage::{get_nft_by_id, save_nft, get_all_nfts, get_nfts_by_project, get_project_by_id, get_tracks_by_project, scan_nfts};
+use crate::storage::{get_track_by_id, get_track_version, get_nft_pins as stored_pins, save_nft_pin};
+use crate::auth::resolve_principal;
+use crate::pagination::{encode_cursor, is_descending, resolve_page};
+use crate::auth::{require_active, require_scope};
+use crate::activity;
+use crate::validation;
+use crate::lineage::{resolve_upstream_royalties, MAX_TOTAL_ROYALTY_PERCENTAGE};
+use crate::projects::{ensure_not_archived, is_project_member, is_project_owner, can_view_project, get_visible_project};
 
 pub fn create_nft(
     project_id: String,
@@ -12,20 +21,39 @@ pub fn create_nft(
     royalty_percentage: u8,
     metadata_uri: String,
 ) -> Result<NFT, String> {
-    let creator = require_authenticated()?;
+    let actor = require_scope(Scope::NftsCreate)?;
+    let creator = actor.account;
     
     // Verify project exists and user has access
     let project = get_project_by_id(&project_id)
         .ok_or_else(|| "Project not found".to_string())?;
 
-    if project.owner != creator && !project.collaborators.contains(&creator.to_string()) {
+    if !is_project_member(&project, creator) {
         return Err("Only project owner or collaborators can create NFTs".to_string());
     }
 
+    ensure_not_archived(&project)?;
+
+    validation::validate_nft_title(&title)?;
+    if let Some(description) = &description {
+        validation::validate_description(description)?;
+    }
+    validation::validate_metadata_uri(&metadata_uri)?;
+
     if royalty_percentage > 50 {
         return Err("Royalty percentage cannot exceed 50%".to_string());
     }
 
+    // Remixes owe their ancestors a share on top of the creator's royalty
+    let upstream_royalties = resolve_upstream_royalties(&project);
+    let upstream_total: u32 = upstream_royalties.iter().map(|share| share.percentage as u32).sum();
+    if royalty_percentage as u32 + upstream_total > MAX_TOTAL_ROYALTY_PERCENTAGE as u32 {
+        return Err(format!(
+            "Royalty percentage plus {}% in upstream shares cannot exceed {}%",
+            upstream_total, MAX_TOTAL_ROYALTY_PERCENTAGE
+        ));
+    }
+
     let nft = NFT {
         id: Uuid::new_v4().to_string(),
         project_id,
@@ -40,44 +68,152 @@ pub fn create_nft(
         is_minted: false,
         is_listed: false,
         opensea_url: None,
+        delegate: actor.delegate,
+        upstream_royalties,
         created_at: time(),
         updated_at: time(),
     };
 
     save_nft(nft.clone());
+    activity::record(&nft.project_id, creator, ActivityKind::NftCreated { nft_id: nft.id.clone() });
     Ok(nft)
 }
 
 pub fn get_nfts() -> Vec<NFT> {
+    let caller = caller_principal();
+
     get_all_nfts()
+        .into_iter()
+        .filter(|nft| is_nft_visible(nft, caller))
+        .collect()
+}
+
+/// Paginated, filterable alternative to `get_nfts` and `get_project_nfts`.
+pub fn list_nfts(query: NftQuery) -> Result<NftPage, String> {
+    let caller = caller_principal();
+    let (after, limit) = resolve_page(&query.page)?;
+    let creator = query.creator.map(resolve_principal);
+
+    let page = scan_nfts(
+        query.sort.unwrap_or(SortField::CreatedAt),
+        after,
+        is_descending(query.direction),
+        limit,
+        |nft| {
+            let matches = creator.is_none_or(|creator| nft.creator == creator)
+                && query.project_id.as_ref().is_none_or(|id| &nft.project_id == id)
+                && query.minted.is_none_or(|minted| nft.is_minted == minted)
+                && query.listed.is_none_or(|listed| nft.is_listed == listed)
+                && is_nft_visible(&nft, caller);
+
+            matches.then_some(nft)
+        },
+    );
+
+    Ok(NftPage {
+        items: page.items,
+        next_cursor: page.last_key.map(|key| encode_cursor(&key)),
+    })
 }
 
 pub fn get_nft(id: String) -> Result<NFT, String> {
     get_nft_by_id(&id)
+        .filter(|nft| is_nft_visible(nft, caller_principal()))
         .ok_or_else(|| "NFT not found".to_string())
 }
 
 pub fn get_project_nfts(project_id: String) -> Vec<NFT> {
-    get_all_nfts()
-        .into_iter()
-        .filter(|nft| nft.project_id == project_id)
-        .collect()
+    if get_visible_project(&project_id, caller_principal()).is_none() {
+        return vec![];
+    }
+
+    get_nfts_by_project(&project_id)
+}
+
+/// Fixes which version of a track the NFT refers to. Pins can change until
+/// the NFT is minted; any track left unpinned is pinned to its active
+/// version at mint time.
+pub fn pin_track_version(nft_id: String, track_id: String, version: u64) -> Result<Vec<PinnedTrackVersion>, String> {
+    let caller = require_active()?;
+
+    let mut nft = get_nft_by_id(&nft_id)
+        .ok_or_else(|| "NFT not found".to_string())?;
+
+    let project = get_project_by_id(&nft.project_id)
+        .ok_or_else(|| "Project not found".to_string())?;
+
+    if nft.creator != caller && !is_project_owner(&project, caller) {
+        return Err("Only NFT creator or project owner can pin track versions".to_string());
+    }
+
+    if nft.is_minted {
+        return Err("Minted NFTs cannot be changed".to_string());
+    }
+
+    ensure_not_archived(&project)?;
+
+    let track = get_track_by_id(&track_id)
+        .filter(|track| track.project_id == nft.project_id)
+        .ok_or_else(|| "Track not found in this NFT's project".to_string())?;
+
+    if get_track_version(&track.id, version).is_none() {
+        return Err("Version not found".to_string());
+    }
+
+    save_nft_pin(&nft_id, &track_id, version);
+    nft.updated_at = time();
+    save_nft(nft);
+
+    Ok(stored_pins(&nft_id))
+}
+
+pub fn get_nft_pins(nft_id: String) -> Result<Vec<PinnedTrackVersion>, String> {
+    get_nft(nft_id).map(|nft| stored_pins(&nft.id))
 }
 
 pub fn mint_nft(id: String, token_id: String, contract_address: String) -> Result<NFT, String> {
-    let caller = require_authenticated()?;
+    let caller = require_active()?;
     
     let mut nft = get_nft_by_id(&id)
         .ok_or_else(|| "NFT not found".to_string())?;
 
-    if nft.creator != caller {
-        return Err("Only NFT creator can mint".to_string());
+    let project = get_project_by_id(&nft.project_id)
+        .ok_or_else(|| "Project not found".to_string())?;
+
+    // The current project owner can mint too, so NFTs survive ownership transfers
+    if nft.creator != caller && !is_project_owner(&project, caller) {
+        return Err("Only NFT creator or project owner can mint".to_string());
     }
 
     if nft.is_minted {
         return Err("NFT already minted".to_string());
     }
 
+    ensure_not_archived(&project)?;
+    validation::validate_token_id(&token_id)?;
+    validation::validate_contract_address(&contract_address)?;
+
+    // Only finished music can be minted
+    let tracks = get_tracks_by_project(&project.id);
+    if tracks.is_empty() {
+        return Err("Project has no tracks to mint".to_string());
+    }
+    if tracks.iter().any(|track| track.status != TrackStatus::Completed) {
+        return Err("All project tracks must be Completed before minting".to_string());
+    }
+
+    // Tracks without an explicit pin are frozen at their current version
+    let pins = stored_pins(&nft.id);
+    for track in &tracks {
+        if pins.iter().any(|pin| pin.track_id == track.id) {
+            continue;
+        }
+
+        if let Some(version) = track.active_version {
+            save_nft_pin(&nft.id, &track.id, version);
+        }
+    }
+
     nft.token_id = Some(token_id.clone());
     nft.contract_address = Some(contract_address.clone());
     nft.is_minted = true;
@@ -85,5 +221,85 @@ pub fn mint_nft(id: String, token_id: String, contract_address: String) -> Resul
     nft.updated_at = time();
 
     save_nft(nft.clone());
+    activity::record(&nft.project_id, caller, ActivityKind::NftMinted { nft_id: nft.id.clone() });
     Ok(nft)
-}
\ No newline at end of file
+}
+
+/// Minted NFTs are public on-chain anyway; drafts follow their project's visibility.
+fn is_nft_visible(nft: &NFT, principal: Principal) -> bool {
+    nft.is_minted
+        || get_project_by_id(&nft.project_id)
+            .is_some_and(|project| can_view_project(&project, principal))
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::{BoundedStorable, Storable};
+    use super::*;
+    use crate::lineage::MAX_UPSTREAM_SHARES;
+    use crate::types::RoyaltyShare;
+
+    #[test]
+    fn an_nft_at_every_limit_fits_its_bound() {
+        // Emoji take four bytes, the most a character can
+        let title = "🎸".repeat(100);
+        let description = "🎸".repeat(1000);
+        let metadata_uri = format!("https://example.com/{}", "a".repeat(492));
+        let token_id = "9".repeat(78);
+        let contract_address = format!("0x{}", "f".repeat(40));
+
+        validation::validate_nft_title(&title).unwrap();
+        validation::validate_description(&description).unwrap();
+        validation::validate_metadata_uri(&metadata_uri).unwrap();
+        validation::validate_token_id(&token_id).unwrap();
+        validation::validate_contract_address(&contract_address).unwrap();
+
+        let principal = Principal::from_slice(&[255; 29]);
+        let share = RoyaltyShare { project_id: Uuid::nil().to_string(), recipient: principal, percentage: 100 };
+        let nft = NFT {
+            id: Uuid::nil().to_string(),
+            project_id: Uuid::nil().to_string(),
+            creator: principal,
+            title,
+            description: Some(description),
+            price: Some(u64::MAX),
+            royalty_percentage: 100,
+            metadata_uri,
+            opensea_url: Some(format!("https://opensea.io/assets/ethereum/{}/{}", contract_address, token_id)),
+            token_id: Some(token_id),
+            contract_address: Some(contract_address),
+            is_minted: true,
+            is_listed: true,
+            delegate: Some(principal),
+            upstream_royalties: vec![share; MAX_UPSTREAM_SHARES],
+            created_at: u64::MAX,
+            updated_at: u64::MAX,
+        };
+
+        let size = nft.to_bytes().len();
+        assert!(size <= NFT::MAX_SIZE as usize, "{} bytes", size);
+    }
+
+    #[test]
+    fn mint_details_must_look_like_evm_values() {
+        assert!(validation::validate_token_id("42").is_ok());
+        assert!(validation::validate_token_id("").is_err());
+        assert!(validation::validate_token_id("0x2a").is_err());
+        assert!(validation::validate_token_id(&"9".repeat(79)).is_err());
+
+        assert!(validation::validate_contract_address(&format!("0x{}", "aB".repeat(20))).is_ok());
+        assert!(validation::validate_contract_address(&"a".repeat(42)).is_err());
+        assert!(validation::validate_contract_address("0x1234").is_err());
+    }
+
+    #[test]
+    fn metadata_uris_point_at_ipfs_or_the_web() {
+        let cid = "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi";
+
+        assert!(validation::validate_metadata_uri(&format!("ipfs://{}/metadata.json", cid)).is_ok());
+        assert!(validation::validate_metadata_uri("https://example.com/metadata.json").is_ok());
+
+        assert!(validation::validate_metadata_uri(cid).is_err());
+        assert!(validation::validate_metadata_uri("ipfs://not-a-cid").is_err());
+    }
+}
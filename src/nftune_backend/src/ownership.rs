@@ -0,0 +1,205 @@
+use candid::Principal;
+use ic_cdk::api::time;
+use uuid::Uuid;
+use crate::types::{ActivityKind, Collaboration, OwnershipTransfer, Project};
+use crate::storage::{
+    get_user_by_principal, get_project_by_id, get_ownership_transfer_by_project, save_ownership_transfer,
+    remove_ownership_transfer, get_collaborations_by_project, save_collaboration, remove_collaboration,
+};
+use crate::auth::{require_active, resolve_principal, caller_principal};
+use crate::collaborations::ensure_collaborator_capacity;
+use crate::history::save_project_with_history;
+use crate::activity;
+use crate::projects::{ensure_not_archived, is_project_owner, is_project_member, can_view_project};
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const DEFAULT_TRANSFER_SECONDS: u64 = 7 * 24 * 60 * 60;
+const MAX_TRANSFER_SECONDS: u64 = 30 * 24 * 60 * 60;
+
+/// Step one: the owner offers the project to another registered user.
+/// Replaces any earlier proposal for the same project.
+pub fn propose_ownership_transfer(
+    project_id: String,
+    to: Principal,
+    require_collaborator_approval: bool,
+    duration_seconds: Option<u64>,
+) -> Result<OwnershipTransfer, String> {
+    let caller = require_active()?;
+    let to = resolve_principal(to);
+
+    let project = get_project_by_id(&project_id)
+        .ok_or_else(|| "Project not found".to_string())?;
+
+    if !is_project_owner(&project, caller) {
+        return Err("Only project owner can transfer ownership".to_string());
+    }
+
+    ensure_not_archived(&project)?;
+
+    if to == caller {
+        return Err("Cannot transfer a project to its current owner".to_string());
+    }
+
+    if get_user_by_principal(to).is_none() {
+        return Err("Recipient is not a registered user".to_string());
+    }
+
+    let duration_seconds = duration_seconds.unwrap_or(DEFAULT_TRANSFER_SECONDS);
+    if duration_seconds == 0 || duration_seconds > MAX_TRANSFER_SECONDS {
+        return Err("Transfer must expire within 30 days".to_string());
+    }
+
+    let transfer = OwnershipTransfer {
+        project_id,
+        from: caller,
+        to,
+        requires_collaborator_approval: require_collaborator_approval,
+        approvals: vec![],
+        proposed_at: time(),
+        expires_at: time() + duration_seconds * NANOS_PER_SECOND,
+    };
+
+    save_ownership_transfer(transfer.clone());
+    Ok(transfer)
+}
+
+/// Records a collaborator's approval of a pending transfer.
+pub fn approve_ownership_transfer(project_id: String) -> Result<OwnershipTransfer, String> {
+    let caller = require_active()?;
+
+    let mut transfer = get_live_transfer(&project_id)?;
+
+    let project = get_project_by_id(&project_id)
+        .ok_or_else(|| "Project not found".to_string())?;
+
+    if !project.collaborators.contains(&caller.to_string()) {
+        return Err("Only collaborators can approve ownership transfers".to_string());
+    }
+
+    if !transfer.approvals.contains(&caller) {
+        transfer.approvals.push(caller);
+        save_ownership_transfer(transfer.clone());
+    }
+
+    Ok(transfer)
+}
+
+/// Step two: the recipient accepts and becomes the owner. A recipient who
+/// was a collaborator stops being one. The previous owner stays on as a
+/// collaborator with no split, so they keep access until the new owner
+/// removes them.
+pub fn accept_ownership_transfer(project_id: String) -> Result<Project, String> {
+    let caller = require_active()?;
+
+    let transfer = get_live_transfer(&project_id)?;
+
+    if transfer.to != caller {
+        return Err("Only the proposed owner can accept this transfer".to_string());
+    }
+
+    let mut project = get_project_by_id(&project_id)
+        .ok_or_else(|| "Project not found".to_string())?;
+
+    // The proposal is void if ownership changed hands some other way
+    if !is_project_owner(&project, transfer.from) {
+        remove_ownership_transfer(&project_id);
+        return Err("Project owner has changed since the transfer was proposed".to_string());
+    }
+
+    ensure_not_archived(&project)?;
+
+    if transfer.requires_collaborator_approval {
+        let pending = missing_approvals(&project, &transfer);
+        if pending > 0 {
+            return Err(format!("Waiting for {} collaborator approval(s)", pending));
+        }
+    }
+
+    let now = time();
+    let previous_owner = project.owner;
+
+    project.collaborators.retain(|collaborator| *collaborator != transfer.to.to_string());
+    ensure_collaborator_capacity(&project)
+        .map_err(|_| "Project has no room to keep the previous owner as a collaborator".to_string())?;
+
+    for collaboration in get_collaborations_by_project(&project_id) {
+        if collaboration.user_principal == transfer.to {
+            remove_collaboration(&collaboration.id);
+        }
+    }
+
+    save_collaboration(Collaboration {
+        id: Uuid::new_v4().to_string(),
+        project_id: project_id.clone(),
+        user_principal: previous_owner,
+        contribution_percentage: 0,
+        role: "Former owner".to_string(),
+        joined_at: now,
+    });
+
+    project.collaborators.push(previous_owner.to_string());
+    project.owner = transfer.to;
+    project.updated_at = now;
+
+    remove_ownership_transfer(&project_id);
+    save_project_with_history(project.clone(), caller);
+    activity::record(
+        &project_id,
+        caller,
+        ActivityKind::OwnershipTransferred { from: previous_owner, to: transfer.to },
+    );
+
+    Ok(project)
+}
+
+/// Withdraws a proposal (owner) or declines it (recipient).
+pub fn cancel_transfer(project_id: String) -> Result<(), String> {
+    let caller = require_active()?;
+
+    let transfer = get_ownership_transfer_by_project(&project_id)
+        .ok_or_else(|| "No pending ownership transfer".to_string())?;
+
+    if transfer.from != caller && transfer.to != caller {
+        return Err("Only the owner or the proposed owner can cancel a transfer".to_string());
+    }
+
+    remove_ownership_transfer(&project_id);
+    Ok(())
+}
+
+/// The pending transfer, for anyone who can see the project and for the
+/// parties to it: the proposed owner and the collaborators who approve it.
+pub fn get_ownership_transfer(project_id: String) -> Option<OwnershipTransfer> {
+    let caller = caller_principal();
+
+    get_ownership_transfer_by_project(&project_id)
+        .filter(|transfer| transfer.expires_at > time())
+        .filter(|transfer| {
+            transfer.to == caller
+                || get_project_by_id(&project_id)
+                    .is_some_and(|project| is_project_member(&project, caller) || can_view_project(&project, caller))
+        })
+}
+
+fn get_live_transfer(project_id: &str) -> Result<OwnershipTransfer, String> {
+    let transfer = get_ownership_transfer_by_project(project_id)
+        .ok_or_else(|| "No pending ownership transfer".to_string())?;
+
+    if transfer.expires_at <= time() {
+        remove_ownership_transfer(project_id);
+        return Err("Ownership transfer has expired".to_string());
+    }
+
+    Ok(transfer)
+}
+
+fn missing_approvals(project: &Project, transfer: &OwnershipTransfer) -> usize {
+    project
+        .collaborators
+        .iter()
+        .filter(|collaborator| **collaborator != transfer.to.to_string())
+        .filter(|collaborator| {
+            !transfer.approvals.iter().any(|approval| approval.to_string() == **collaborator)
+        })
+        .count()
+}
@@ -0,0 +1,55 @@
+use crate::types::{PageRequest, SortDirection};
+
+const DEFAULT_PAGE_SIZE: u32 = 20;
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// Cursors are hex-encoded storage keys. Clients should treat them as opaque.
+pub fn encode_cursor(key: &str) -> String {
+    key.bytes().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn decode_cursor(cursor: &str) -> Result<String, String> {
+    let invalid = || "Invalid cursor".to_string();
+
+    // Checking the digits first also keeps the slicing below on character boundaries
+    if !cursor.len().is_multiple_of(2) || !cursor.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&cursor[i..i + 2], 16).map_err(|_| invalid()))
+        .collect::<Result<Vec<u8>, String>>()?;
+
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
+/// Decoded cursor and clamped page size for a request.
+pub fn resolve_page(page: &PageRequest) -> Result<(Option<String>, usize), String> {
+    let after = page.cursor.as_deref().map(decode_cursor).transpose()?;
+    let limit = page.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE) as usize;
+    Ok((after, limit))
+}
+
+/// List queries default to newest first.
+pub fn is_descending(direction: Option<SortDirection>) -> bool {
+    direction != Some(SortDirection::Ascending)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors_round_trip() {
+        let key = "00000000000000000042:project-id";
+        assert_eq!(decode_cursor(&encode_cursor(key)).unwrap(), key);
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        for cursor in ["abc", "zz", "é1", "1é", "+1"] {
+            assert!(decode_cursor(cursor).is_err(), "{:?} should be rejected", cursor);
+        }
+    }
+}
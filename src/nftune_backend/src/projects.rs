@@ -1,11 +1,39 @@
 use ic_cdk::api::time;
 use uuid::Uuid;
-use crate::types::{Project, Track, TrackStatus};
-use crate::storage::{get_project_by_id, save_project, get_all_projects, save_track, get_all_tracks};
-use crate::auth::require_authenticated;
+use candid::Principal;
+use crate::types::{
+    Project, ProjectPage, ProjectQuery, ProjectVisibility, Role, Scope, SortField, Track, TrackPage,
+    TrackMetadata, TrackQuery, TrackStatus, ActivityKind,
+};
+use crate::storage::{
+    get_project_by_id, get_all_projects, remove_project, save_track, get_tracks_by_project,
+    remove_track, get_nfts_by_project, remove_nft, get_collaborations_by_project, remove_collaboration,
+    remove_ownership_transfer, remove_project_revisions, remove_project_activity, scan_projects,
+    scan_project_tracks, get_projects_by_owner, get_invitations_by_project, remove_invitation,
+};
+use crate::auth::{require_active, require_scope, caller_principal, get_role, resolve_principal};
+use crate::pagination::{encode_cursor, is_descending, resolve_page};
+use crate::validation;
+use crate::history::save_project_with_history;
+use crate::activity;
+use crate::tracks::record_initial_version;
 
-pub fn create_project(name: String, description: Option<String>) -> Result<Project, String> {
-    let owner = require_authenticated()?;
+/// Keeps `Project.tracks` within the project's storage bound.
+const MAX_TRACKS_PER_PROJECT: usize = 50;
+
+/// New projects are public unless a visibility is given, so clients that
+/// predate visibility keep creating public projects.
+pub fn create_project(
+    name: String,
+    description: Option<String>,
+    visibility: Option<ProjectVisibility>,
+) -> Result<Project, String> {
+    let owner = require_active()?;
+
+    validation::validate_name(&name)?;
+    if let Some(description) = &description {
+        validation::validate_description(description)?;
+    }
     
     let project = Project {
         id: Uuid::new_v4().to_string(),
@@ -15,80 +43,466 @@ pub fn create_project(name: String, description: Option<String>) -> Result<Proje
         collaborators: vec![],
         tracks: vec![],
         nfts: vec![],
+        visibility: visibility.unwrap_or(ProjectVisibility::Public),
+        genres: vec![],
+        moods: vec![],
+        tags: vec![],
+        allow_remixes: false,
+        upstream_royalty_percentage: 0,
+        parent_project_id: None,
+        upstream_royalties: vec![],
+        archived_at: None,
         created_at: time(),
         updated_at: time(),
     };
 
-    save_project(project.clone());
+    save_project_with_history(project.clone(), owner);
+    activity::record(&project.id, owner, ActivityKind::ProjectCreated);
     Ok(project)
 }
 
+/// Lists public projects plus any the caller owns or collaborates on.
+/// Archived and unlisted projects are left out.
 pub fn get_projects() -> Vec<Project> {
+    let caller = caller_principal();
+
     get_all_projects()
+        .into_iter()
+        .filter(|project| is_listed_for(project, caller))
+        .collect()
+}
+
+/// Paginated, filterable alternative to `get_projects`.
+pub fn list_projects(query: ProjectQuery) -> Result<ProjectPage, String> {
+    list_projects_for(query, caller_principal())
+}
+
+fn list_projects_for(query: ProjectQuery, caller: Principal) -> Result<ProjectPage, String> {
+    let (after, limit) = resolve_page(&query.page)?;
+    let owner = query.owner.map(resolve_principal);
+    let archived = query.archived.unwrap_or(false);
+
+    let page = scan_projects(
+        query.sort.unwrap_or(SortField::CreatedAt),
+        after,
+        is_descending(query.direction),
+        limit,
+        |project| {
+            if owner.is_some_and(|owner| !is_project_owner(&project, owner)) {
+                return None;
+            }
+
+            let visible = if archived {
+                project.archived_at.is_some() && can_view_project(&project, caller)
+            } else {
+                is_listed_for(&project, caller)
+            };
+
+            visible.then_some(project)
+        },
+    );
+
+    Ok(ProjectPage {
+        items: page.items,
+        next_cursor: page.last_key.map(|key| encode_cursor(&key)),
+    })
 }
 
 pub fn get_project(id: String) -> Result<Project, String> {
-    get_project_by_id(&id)
+    get_visible_project(&id, caller_principal())
         .ok_or_else(|| "Project not found".to_string())
 }
 
-pub fn update_project(id: String, name: Option<String>, description: Option<String>) -> Result<Project, String> {
-    let caller = require_authenticated()?;
+/// The caller's own archived projects, so they can be found and restored.
+pub fn get_archived_projects() -> Result<Vec<Project>, String> {
+    let caller = require_active()?;
+
+    Ok(get_projects_by_owner(caller)
+        .into_iter()
+        .filter(|project| project.archived_at.is_some())
+        .collect())
+}
+
+/// Makes a project read-only and hides it from listings.
+pub fn archive_project(id: String) -> Result<Project, String> {
+    let caller = require_active()?;
+
+    let mut project = get_project_by_id(&id)
+        .ok_or_else(|| "Project not found".to_string())?;
+
+    require_owner_or_admin(&project, caller, "archive")?;
+    ensure_not_archived(&project)?;
+
+    project.archived_at = Some(time());
+    project.updated_at = time();
+    save_project_with_history(project.clone(), caller);
+    activity::record(&id, caller, ActivityKind::ProjectArchived);
+    Ok(project)
+}
+
+pub fn restore_project(id: String) -> Result<Project, String> {
+    let caller = require_active()?;
+
+    let mut project = get_project_by_id(&id)
+        .ok_or_else(|| "Project not found".to_string())?;
+
+    require_owner_or_admin(&project, caller, "restore")?;
+
+    if project.archived_at.is_none() {
+        return Err("Project is not archived".to_string());
+    }
+
+    project.archived_at = None;
+    project.updated_at = time();
+    save_project_with_history(project.clone(), caller);
+    activity::record(&id, caller, ActivityKind::ProjectRestored);
+    Ok(project)
+}
+
+/// Permanently deletes a project with its tracks, NFTs and collaborations.
+/// Projects with minted NFTs can only be archived, since the tokens
+/// reference them.
+pub fn delete_project(id: String) -> Result<(), String> {
+    let caller = require_active()?;
+
+    let project = get_project_by_id(&id)
+        .ok_or_else(|| "Project not found".to_string())?;
+
+    require_owner_or_admin(&project, caller, "delete")?;
+
+    let nfts = get_nfts_by_project(&id);
+
+    if nfts.iter().any(|nft| nft.is_minted) {
+        return Err("Projects with minted NFTs cannot be deleted; archive it instead".to_string());
+    }
+
+    for nft in nfts {
+        remove_nft(&nft.id);
+    }
+
+    for track in get_tracks_by_project(&id) {
+        remove_track(&track.id);
+    }
+
+    for collaboration in get_collaborations_by_project(&id) {
+        remove_collaboration(&collaboration.id);
+    }
+
+    for invitation in get_invitations_by_project(&id) {
+        remove_invitation(&invitation.id);
+    }
+
+    remove_ownership_transfer(&id);
+    remove_project_revisions(&id);
+    remove_project_activity(&id);
+    remove_project(&id);
+    Ok(())
+}
+
+/// Replaces the project's genres, moods and tags. Labels are trimmed,
+/// lowercased and deduplicated so they match search tokens.
+pub fn set_project_tags(
+    id: String,
+    genres: Vec<String>,
+    moods: Vec<String>,
+    tags: Vec<String>,
+) -> Result<Project, String> {
+    let caller = require_active()?;
+
+    let mut project = get_project_by_id(&id)
+        .ok_or_else(|| "Project not found".to_string())?;
+
+    if !is_project_owner(&project, caller) {
+        return Err("Only project owner can update project".to_string());
+    }
+
+    ensure_not_archived(&project)?;
+
+    validation::validate_tags("genres", &genres)?;
+    validation::validate_tags("moods", &moods)?;
+    validation::validate_tags("tags", &tags)?;
+
+    project.genres = normalize_labels(genres);
+    project.moods = normalize_labels(moods);
+    project.tags = normalize_labels(tags);
+    project.updated_at = time();
+
+    save_project_with_history(project.clone(), caller);
+    activity::record(&id, caller, ActivityKind::ProjectUpdated);
+    Ok(project)
+}
+
+fn normalize_labels(labels: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+
+    for label in labels {
+        let label = label.trim().to_lowercase();
+        if !normalized.contains(&label) {
+            normalized.push(label);
+        }
+    }
+
+    normalized
+}
+
+pub fn update_project(
+    id: String,
+    name: Option<String>,
+    description: Option<String>,
+    visibility: Option<ProjectVisibility>,
+) -> Result<Project, String> {
+    let caller = require_active()?;
     
     let mut project = get_project_by_id(&id)
         .ok_or_else(|| "Project not found".to_string())?;
 
     // Check if caller is the owner
-    if project.owner != caller {
+    if !is_project_owner(&project, caller) {
         return Err("Only project owner can update project".to_string());
     }
 
+    ensure_not_archived(&project)?;
+
     if let Some(new_name) = name {
+        validation::validate_name(&new_name)?;
         project.name = new_name;
     }
     
     if let Some(new_description) = description {
+        validation::validate_description(&new_description)?;
         project.description = Some(new_description);
     }
+
+    if let Some(new_visibility) = visibility {
+        project.visibility = new_visibility;
+    }
     
     project.updated_at = time();
-    save_project(project.clone());
+    save_project_with_history(project.clone(), caller);
+    activity::record(&id, caller, ActivityKind::ProjectUpdated);
     Ok(project)
 }
 
 pub fn add_track(project_id: String, name: String, ipfs_hash: String, duration: u64) -> Result<Track, String> {
-    let caller = require_authenticated()?;
+    let actor = require_scope(Scope::TracksWrite)?;
+    let caller = actor.account;
     
     let mut project = get_project_by_id(&project_id)
         .ok_or_else(|| "Project not found".to_string())?;
 
     // Check if caller is owner or collaborator
-    if project.owner != caller && !project.collaborators.contains(&caller.to_string()) {
+    if !is_project_member(&project, caller) {
         return Err("Only project owner or collaborators can add tracks".to_string());
     }
 
-    let track = Track {
+    ensure_not_archived(&project)?;
+    validation::validate_name(&name)?;
+    validation::validate_cid(&ipfs_hash)?;
+
+    if project.tracks.len() >= MAX_TRACKS_PER_PROJECT {
+        return Err(format!("A project cannot have more than {} tracks", MAX_TRACKS_PER_PROJECT));
+    }
+
+    let mut track = Track {
         id: Uuid::new_v4().to_string(),
         project_id: project_id.clone(),
         name,
         ipfs_hash,
         duration,
         status: TrackStatus::Draft,
+        delegate: actor.delegate,
+        active_version: None,
+        metadata: TrackMetadata::default(),
         created_at: time(),
     };
+    record_initial_version(&mut track, caller);
 
     project.tracks.push(track.id.clone());
     project.updated_at = time();
     
     save_track(track.clone());
-    save_project(project);
+    save_project_with_history(project, caller);
+    activity::record(&project_id, caller, ActivityKind::TrackAdded { track_id: track.id.clone() });
     
     Ok(track)
 }
 
 pub fn get_project_tracks(project_id: String) -> Vec<Track> {
-    get_all_tracks()
-        .into_iter()
-        .filter(|track| track.project_id == project_id)
-        .collect()
-}
\ No newline at end of file
+    if get_visible_project(&project_id, caller_principal()).is_none() {
+        return vec![];
+    }
+
+    get_tracks_by_project(&project_id)
+}
+
+/// Paginated alternative to `get_project_tracks`, optionally filtered by status.
+pub fn list_project_tracks(project_id: String, query: TrackQuery) -> Result<TrackPage, String> {
+    if get_visible_project(&project_id, caller_principal()).is_none() {
+        return Err("Project not found".to_string());
+    }
+
+    let (after, limit) = resolve_page(&query.page)?;
+
+    let page = scan_project_tracks(&project_id, after, limit, |track| {
+        query.status.as_ref().is_none_or(|status| track.status == *status).then_some(track)
+    });
+
+    Ok(TrackPage {
+        items: page.items,
+        next_cursor: page.last_key.map(|key| encode_cursor(&key)),
+    })
+}
+
+/// Ownership can change hands through `ownership::accept_ownership_transfer`,
+/// so owner checks must always read the stored project.
+pub fn is_project_owner(project: &Project, principal: Principal) -> bool {
+    project.owner == principal
+}
+
+pub fn is_project_member(project: &Project, principal: Principal) -> bool {
+    is_project_owner(project, principal) || project.collaborators.contains(&principal.to_string())
+}
+
+fn require_owner_or_admin(project: &Project, caller: Principal, action: &str) -> Result<(), String> {
+    if !is_project_owner(project, caller) && get_role(caller) != Role::Admin {
+        return Err(format!("Only project owner can {} project", action));
+    }
+
+    Ok(())
+}
+
+/// Read access: owners and moderators see everything; collaborators see
+/// all but private projects; anyone with the id sees unlisted and public
+/// projects unless they are archived.
+pub fn can_view_project(project: &Project, principal: Principal) -> bool {
+    if is_project_owner(project, principal) || get_role(principal) >= Role::Moderator {
+        return true;
+    }
+
+    let collaborator = project.collaborators.contains(&principal.to_string());
+
+    match project.visibility {
+        ProjectVisibility::Private => false,
+        ProjectVisibility::CollaboratorsOnly => collaborator,
+        ProjectVisibility::Unlisted | ProjectVisibility::Public => {
+            collaborator || project.archived_at.is_none()
+        }
+    }
+}
+
+/// Whether the project belongs in listings for `principal`.
+pub fn is_listed_for(project: &Project, principal: Principal) -> bool {
+    project.archived_at.is_none()
+        && can_view_project(project, principal)
+        && (project.visibility == ProjectVisibility::Public || is_project_member(project, principal))
+}
+
+pub fn get_visible_project(id: &str, principal: Principal) -> Option<Project> {
+    get_project_by_id(id).filter(|project| can_view_project(project, principal))
+}
+
+/// Archived projects are read-only.
+pub fn ensure_not_archived(project: &Project) -> Result<(), String> {
+    if project.archived_at.is_some() {
+        return Err("Project is archived".to_string());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{save_project, save_role};
+
+    fn owner() -> Principal {
+        Principal::from_slice(&[1])
+    }
+
+    fn collaborator() -> Principal {
+        Principal::from_slice(&[2])
+    }
+
+    fn stranger() -> Principal {
+        Principal::from_slice(&[3])
+    }
+
+    fn project(id: &str, visibility: ProjectVisibility, created_at: u64) -> Project {
+        Project {
+            id: id.to_string(),
+            owner: owner(),
+            name: id.to_string(),
+            description: None,
+            collaborators: vec![collaborator().to_string()],
+            tracks: vec![],
+            nfts: vec![],
+            visibility,
+            genres: vec![],
+            moods: vec![],
+            tags: vec![],
+            allow_remixes: false,
+            upstream_royalty_percentage: 0,
+            parent_project_id: None,
+            upstream_royalties: vec![],
+            archived_at: None,
+            created_at,
+            updated_at: created_at,
+        }
+    }
+
+    /// Who of owner, collaborator, stranger and anonymous can read `project`.
+    fn viewers(project: &Project) -> [bool; 4] {
+        [owner(), collaborator(), stranger(), Principal::anonymous()].map(|principal| can_view_project(project, principal))
+    }
+
+    #[test]
+    fn each_visibility_is_readable_by_its_audience() {
+        assert_eq!(viewers(&project("p", ProjectVisibility::Private, 0)), [true, false, false, false]);
+        assert_eq!(viewers(&project("p", ProjectVisibility::CollaboratorsOnly, 0)), [true, true, false, false]);
+        assert_eq!(viewers(&project("p", ProjectVisibility::Unlisted, 0)), [true, true, true, true]);
+        assert_eq!(viewers(&project("p", ProjectVisibility::Public, 0)), [true, true, true, true]);
+    }
+
+    #[test]
+    fn archived_projects_are_hidden_from_outsiders_only() {
+        let archived = Project { archived_at: Some(5), ..project("p", ProjectVisibility::Public, 0) };
+
+        assert_eq!(viewers(&archived), [true, true, false, false]);
+    }
+
+    #[test]
+    fn moderators_can_read_every_project() {
+        save_role(stranger(), Role::Moderator);
+
+        assert!(can_view_project(&project("p", ProjectVisibility::Private, 0), stranger()));
+    }
+
+    #[test]
+    fn listings_leave_out_unlisted_projects_for_outsiders() {
+        let unlisted = project("p", ProjectVisibility::Unlisted, 0);
+
+        assert!(is_listed_for(&unlisted, owner()));
+        assert!(is_listed_for(&unlisted, collaborator()));
+        assert!(!is_listed_for(&unlisted, stranger()));
+    }
+
+    #[test]
+    fn listed_pages_hold_only_what_the_caller_may_see() {
+        save_project(project("public", ProjectVisibility::Public, 1));
+        save_project(project("unlisted", ProjectVisibility::Unlisted, 2));
+        save_project(project("private", ProjectVisibility::Private, 3));
+        save_project(Project { archived_at: Some(5), ..project("archived", ProjectVisibility::Public, 4) });
+
+        let ids = |query: ProjectQuery, caller: Principal| -> Vec<String> {
+            let page = list_projects_for(query, caller).unwrap();
+            page.items.into_iter().map(|project| project.id).collect()
+        };
+
+        assert_eq!(ids(ProjectQuery::default(), stranger()), ["public"]);
+        assert_eq!(ids(ProjectQuery::default(), owner()), ["private", "unlisted", "public"]);
+
+        let archived = ProjectQuery { archived: Some(true), ..ProjectQuery::default() };
+        assert!(ids(archived.clone(), stranger()).is_empty());
+        assert_eq!(ids(archived, owner()), ["archived"]);
+    }
+}
@@ -0,0 +1,59 @@
+use std::cell::RefCell;
+use std::time::Duration;
+use ic_cdk::api::management_canister::main::raw_rand;
+use sha2::{Digest, Sha256};
+
+const RESEED_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+thread_local! {
+    // Seeded from the subnet's randomness beacon; lives on the heap only, so
+    // it is never readable from stable memory and is re-seeded after upgrades
+    static SEED: RefCell<Option<[u8; 32]>> = const { RefCell::new(None) };
+    static COUNTER: RefCell<u64> = const { RefCell::new(0) };
+}
+
+/// Fetches fresh entropy from the management canister. Runs from a timer
+/// after install and upgrade, because `raw_rand` can't be awaited in either
+/// hook; a failed call is retried shortly after.
+pub fn schedule_seeding() {
+    ic_cdk_timers::set_timer(Duration::ZERO, || ic_cdk::spawn(seed()));
+}
+
+async fn seed() {
+    match raw_rand().await {
+        Ok((bytes,)) => mix_seed(&bytes),
+        Err(_) => {
+            ic_cdk_timers::set_timer(RESEED_RETRY_DELAY, || ic_cdk::spawn(seed()));
+        }
+    }
+}
+
+/// Folds `entropy` into the seed, keeping whatever was mixed in before.
+pub fn mix_seed(entropy: &[u8]) {
+    SEED.with(|seed| {
+        let mut hasher = Sha256::new();
+        if let Some(current) = *seed.borrow() {
+            hasher.update(current);
+        }
+        hasher.update(entropy);
+        *seed.borrow_mut() = Some(hasher.finalize().into());
+    });
+}
+
+/// Returns 32 unpredictable bytes, distinct on every call. Fails until the
+/// first `raw_rand` call after install or upgrade has completed.
+pub fn random_bytes() -> Result<[u8; 32], String> {
+    let seed = SEED.with(|seed| *seed.borrow())
+        .ok_or_else(|| "Randomness is not available yet, try again shortly".to_string())?;
+
+    let counter = COUNTER.with(|counter| {
+        let mut counter = counter.borrow_mut();
+        *counter += 1;
+        *counter
+    });
+
+    let mut hasher = Sha256::new();
+    hasher.update(seed);
+    hasher.update(counter.to_be_bytes());
+    Ok(hasher.finalize().into())
+}
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use candid::Principal;
+use crate::types::{Project, SearchHit, SearchKind, SearchPage, SearchQuery, SearchResult, Track, NFT};
+use crate::storage::{search_postings, get_project_by_id, get_track_by_id, get_nft_by_id};
+use crate::auth::caller_principal;
+use crate::pagination::{encode_cursor, resolve_page};
+use crate::projects::is_listed_for;
+
+const MIN_TOKEN_LENGTH: usize = 2;
+const MAX_TOKEN_LENGTH: usize = 32;
+const MAX_QUERY_TERMS: usize = 8;
+
+/// Postings read per query term, so a very common prefix can't exhaust
+/// the instruction limit.
+const MAX_POSTINGS_PER_TERM: usize = 5_000;
+
+const TITLE_WEIGHT: u32 = 4;
+const TAG_WEIGHT: u32 = 3;
+const DESCRIPTION_WEIGHT: u32 = 1;
+
+/// Whole-word matches rank above prefix matches.
+const EXACT_MATCH_MULTIPLIER: u32 = 2;
+
+/// Lowercased words of at least two characters, without duplicates.
+/// Tokens never contain ':', which the index uses as a separator.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+
+    for word in text.split(|c: char| !c.is_alphanumeric()) {
+        let token: String = word.to_lowercase().chars().take(MAX_TOKEN_LENGTH).collect();
+
+        if token.chars().count() >= MIN_TOKEN_LENGTH && !tokens.contains(&token) {
+            tokens.push(token);
+        }
+    }
+
+    tokens
+}
+
+/// Index key for a document, e.g. "project:<id>".
+pub fn doc_key(kind: SearchKind, id: &str) -> String {
+    let kind = match kind {
+        SearchKind::Project => "project",
+        SearchKind::Track => "track",
+        SearchKind::Nft => "nft",
+    };
+
+    format!("{}:{}", kind, id)
+}
+
+pub fn posting_key(token: &str, doc: &str) -> String {
+    format!("{}:{}", token, doc)
+}
+
+fn parse_doc_key(key: &str) -> Option<(SearchKind, &str)> {
+    let (kind, id) = key.split_once(':')?;
+
+    let kind = match kind {
+        "project" => SearchKind::Project,
+        "track" => SearchKind::Track,
+        "nft" => SearchKind::Nft,
+        _ => return None,
+    };
+
+    Some((kind, id))
+}
+
+pub fn project_terms(project: &Project) -> Vec<(String, u32)> {
+    let labels = [&project.genres, &project.moods, &project.tags]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    weighted_terms(&[
+        (&project.name, TITLE_WEIGHT),
+        (&labels, TAG_WEIGHT),
+        (project.description.as_deref().unwrap_or_default(), DESCRIPTION_WEIGHT),
+    ])
+}
+
+pub fn track_terms(track: &Track) -> Vec<(String, u32)> {
+    weighted_terms(&[
+        (&track.name, TITLE_WEIGHT),
+        (track.metadata.genre.as_deref().unwrap_or_default(), TAG_WEIGHT),
+    ])
+}
+
+pub fn nft_terms(nft: &NFT) -> Vec<(String, u32)> {
+    weighted_terms(&[
+        (&nft.title, TITLE_WEIGHT),
+        (nft.description.as_deref().unwrap_or_default(), DESCRIPTION_WEIGHT),
+    ])
+}
+
+/// A token found in several fields is weighted by all of them.
+fn weighted_terms(fields: &[(&str, u32)]) -> Vec<(String, u32)> {
+    let mut terms: Vec<(String, u32)> = Vec::new();
+
+    for (text, weight) in fields {
+        for token in tokenize(text) {
+            match terms.iter_mut().find(|(term, _)| *term == token) {
+                Some((_, total)) => *total += weight,
+                None => terms.push((token, *weight)),
+            }
+        }
+    }
+
+    terms
+}
+
+/// Ranked search over project names, descriptions and labels, track names
+/// and genres, and NFT titles. Every query term has to match a word, either whole or as
+/// a prefix. Results the caller couldn't find through listings are skipped.
+pub fn search(query: SearchQuery) -> Result<SearchPage, String> {
+    search_as(query, caller_principal())
+}
+
+fn search_as(query: SearchQuery, caller: Principal) -> Result<SearchPage, String> {
+    let (after, limit) = resolve_page(&query.page)?;
+
+    // Rankings aren't stable keys, so the cursor is an offset into them
+    let offset = match after {
+        Some(offset) => offset.parse::<usize>().map_err(|_| "Invalid cursor".to_string())?,
+        None => 0,
+    };
+
+    let terms = tokenize(&query.text);
+
+    if terms.is_empty() {
+        return Err("Search text must contain a word of at least 2 characters".to_string());
+    }
+
+    if terms.len() > MAX_QUERY_TERMS {
+        return Err(format!("Search text cannot contain more than {} words", MAX_QUERY_TERMS));
+    }
+
+    let wanted = |kind: SearchKind| query.kinds.as_ref().is_none_or(|kinds| kinds.contains(&kind));
+
+    let mut scores: Option<HashMap<String, u32>> = None;
+
+    for term in &terms {
+        let mut matches: HashMap<String, u32> = HashMap::new();
+
+        for (token, doc, weight) in search_postings(term, MAX_POSTINGS_PER_TERM) {
+            if !parse_doc_key(&doc).is_some_and(|(kind, _)| wanted(kind)) {
+                continue;
+            }
+
+            let score = if &token == term { weight * EXACT_MATCH_MULTIPLIER } else { weight };
+            let best = matches.entry(doc).or_insert(0);
+            *best = (*best).max(score);
+        }
+
+        // Keep only documents that matched every term so far
+        scores = Some(match scores {
+            None => matches,
+            Some(scores) => scores
+                .into_iter()
+                .filter_map(|(doc, score)| matches.get(&doc).map(|extra| (doc, score + extra)))
+                .collect(),
+        });
+    }
+
+    let mut ranked: Vec<(String, u32)> = scores.unwrap_or_default().into_iter().collect();
+    ranked.sort_by(|(a_doc, a_score), (b_doc, b_score)| b_score.cmp(a_score).then_with(|| a_doc.cmp(b_doc)));
+
+    let mut visible = ranked
+        .iter()
+        .filter_map(|(doc, score)| {
+            load_hit(doc, caller).map(|hit| SearchResult { score: *score, hit })
+        })
+        .skip(offset);
+
+    let items: Vec<SearchResult> = visible.by_ref().take(limit).collect();
+    let has_more = visible.next().is_some();
+
+    Ok(SearchPage {
+        next_cursor: has_more.then(|| encode_cursor(&(offset + items.len()).to_string())),
+        items,
+    })
+}
+
+/// Loads a matched document if it would show up in the caller's listings.
+fn load_hit(doc: &str, caller: Principal) -> Option<SearchHit> {
+    let listed = |project_id: &str| {
+        get_project_by_id(project_id).is_some_and(|project| is_listed_for(&project, caller))
+    };
+
+    match parse_doc_key(doc)? {
+        (SearchKind::Project, id) => get_project_by_id(id)
+            .filter(|project| is_listed_for(project, caller))
+            .map(SearchHit::Project),
+        (SearchKind::Track, id) => get_track_by_id(id)
+            .filter(|track| listed(&track.project_id))
+            .map(SearchHit::Track),
+        // Minted NFTs are public on-chain regardless of their project
+        (SearchKind::Nft, id) => get_nft_by_id(id)
+            .filter(|nft| nft.is_minted || listed(&nft.project_id))
+            .map(SearchHit::Nft),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{PageRequest, ProjectVisibility};
+    use crate::storage::save_project;
+
+    fn owner() -> Principal {
+        Principal::from_slice(&[1])
+    }
+
+    fn stranger() -> Principal {
+        Principal::from_slice(&[2])
+    }
+
+    fn save(id: &str, name: &str, description: &str, visibility: ProjectVisibility) {
+        save_project(Project {
+            id: id.to_string(),
+            owner: owner(),
+            name: name.to_string(),
+            description: Some(description.to_string()),
+            collaborators: vec![],
+            tracks: vec![],
+            nfts: vec![],
+            visibility,
+            genres: vec![],
+            moods: vec![],
+            tags: vec![],
+            allow_remixes: false,
+            upstream_royalty_percentage: 0,
+            parent_project_id: None,
+            upstream_royalties: vec![],
+            archived_at: None,
+            created_at: 0,
+            updated_at: 0,
+        });
+    }
+
+    fn query(text: &str, limit: Option<u32>, cursor: Option<String>) -> SearchQuery {
+        SearchQuery { text: text.to_string(), kinds: None, page: PageRequest { cursor, limit } }
+    }
+
+    /// Ids of the projects found for `text`, best first.
+    fn found(text: &str, caller: Principal) -> Vec<String> {
+        search_as(query(text, None, None), caller)
+            .unwrap()
+            .items
+            .into_iter()
+            .filter_map(|result| match result.hit {
+                SearchHit::Project(project) => Some(project.id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tokens_are_lowercased_distinct_words_of_two_characters_or_more() {
+        assert_eq!(tokenize("Night DRIVE, a night-drive!"), ["night", "drive"]);
+    }
+
+    #[test]
+    fn titles_outrank_descriptions_and_whole_words_outrank_prefixes() {
+        save("described", "Ambient", "a night drive", ProjectVisibility::Public);
+        save("titled", "Night Drive", "", ProjectVisibility::Public);
+        save("prefixed", "Nightfall", "", ProjectVisibility::Public);
+
+        assert_eq!(found("night", stranger()), ["titled", "prefixed", "described"]);
+    }
+
+    #[test]
+    fn every_term_has_to_match() {
+        save("both", "Night Drive", "", ProjectVisibility::Public);
+        save("one", "Night Swim", "", ProjectVisibility::Public);
+
+        assert_eq!(found("night drive", stranger()), ["both"]);
+    }
+
+    #[test]
+    fn results_only_include_projects_listed_for_the_caller() {
+        save("public", "Night Drive", "", ProjectVisibility::Public);
+        save("unlisted", "Night Drive", "", ProjectVisibility::Unlisted);
+        save("private", "Night Drive", "", ProjectVisibility::Private);
+
+        assert_eq!(found("drive", stranger()), ["public"]);
+        assert_eq!(found("drive", owner()), ["private", "public", "unlisted"]);
+    }
+
+    #[test]
+    fn pages_continue_after_hidden_results() {
+        save("a", "Night Drive", "", ProjectVisibility::Public);
+        save("b", "Night Drive", "", ProjectVisibility::Private);
+        save("c", "Night Drive", "", ProjectVisibility::Public);
+
+        let first = search_as(query("drive", Some(1), None), stranger()).unwrap();
+        assert_eq!(first.items.len(), 1);
+
+        let second = search_as(query("drive", Some(1), first.next_cursor), stranger()).unwrap();
+        assert_eq!(second.items.len(), 1);
+        assert!(second.next_cursor.is_none());
+    }
+
+    #[test]
+    fn queries_need_a_searchable_word() {
+        assert!(search_as(query("a !", None, None), stranger()).is_err());
+    }
+}
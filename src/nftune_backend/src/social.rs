@@ -0,0 +1,104 @@
+use candid::Principal;
+use ic_cdk::api::time;
+use crate::types::{FollowEntry, FollowPage};
+use crate::storage::{
+    get_user_by_principal, save_user, is_following, save_follow, remove_follow,
+    get_following_page, get_followers_page,
+};
+use crate::auth::{require_active, resolve_principal};
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 200;
+
+pub fn follow_user(followee: Principal) -> Result<(), String> {
+    let follower = require_active()?;
+    let followee = resolve_principal(followee);
+
+    if follower == followee {
+        return Err("Cannot follow yourself".to_string());
+    }
+
+    let mut followee_user = get_user_by_principal(followee)
+        .ok_or_else(|| "User not found".to_string())?;
+
+    if is_following(follower, followee) {
+        return Err("Already following this user".to_string());
+    }
+
+    let mut follower_user = get_user_by_principal(follower)
+        .ok_or_else(|| "User not found".to_string())?;
+
+    save_follow(follower, followee, time());
+
+    follower_user.following_count += 1;
+    followee_user.follower_count += 1;
+    save_user(follower_user);
+    save_user(followee_user);
+
+    Ok(())
+}
+
+pub fn unfollow_user(followee: Principal) -> Result<(), String> {
+    let follower = require_active()?;
+    let followee = resolve_principal(followee);
+
+    if !is_following(follower, followee) {
+        return Err("Not following this user".to_string());
+    }
+
+    remove_follow(follower, followee);
+    adjust_counts(follower, followee);
+
+    Ok(())
+}
+
+/// Decrements the cached counts after an edge is removed, on whichever
+/// side still has a `User` record.
+pub fn adjust_counts(follower: Principal, followee: Principal) {
+    if let Some(mut user) = get_user_by_principal(follower) {
+        user.following_count = user.following_count.saturating_sub(1);
+        save_user(user);
+    }
+
+    if let Some(mut user) = get_user_by_principal(followee) {
+        user.follower_count = user.follower_count.saturating_sub(1);
+        save_user(user);
+    }
+}
+
+pub fn get_followers(principal: Principal, start_after: Option<Principal>, limit: Option<u32>) -> FollowPage {
+    let principal = resolve_principal(principal);
+    let limit = page_size(limit);
+    to_page(get_followers_page(principal, start_after, limit), limit)
+}
+
+pub fn get_following(principal: Principal, start_after: Option<Principal>, limit: Option<u32>) -> FollowPage {
+    let principal = resolve_principal(principal);
+    let limit = page_size(limit);
+    to_page(get_following_page(principal, start_after, limit), limit)
+}
+
+pub fn is_following_user(follower: Principal, followee: Principal) -> bool {
+    is_following(resolve_principal(follower), resolve_principal(followee))
+}
+
+fn page_size(limit: Option<u32>) -> usize {
+    limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE) as usize
+}
+
+fn to_page(edges: Vec<(Principal, u64)>, limit: usize) -> FollowPage {
+    // A short page means the range scan ran out of edges
+    let next_cursor = if edges.len() == limit {
+        edges.last().map(|(principal, _)| *principal)
+    } else {
+        None
+    };
+
+    FollowPage {
+        entries: edges
+            .into_iter()
+            .map(|(principal, followed_at)| FollowEntry { principal, followed_at })
+            .collect(),
+        next_cursor,
+    }
+}
@@ -1,7 +1,21 @@
 use candid::Principal;
+use std::ops::Bound;
 use ic_cdk::api::time;
+use ic_stable_structures::{StableBTreeMap, Storable};
 use crate::types::*;
-use crate::{USERS, PROJECTS, NFTS, COLLABORATIONS, TRACKS};
+use crate::Memory;
+use crate::{
+    USERS, LEGACY_USERS, PROJECTS, LEGACY_PROJECTS, NFTS, LEGACY_NFTS, COLLABORATIONS, TRACKS, HANDLES, ROLES, SUSPENSIONS, LINKED_PRINCIPALS,
+    LINK_CHALLENGES, DELEGATIONS, EMAIL_VERIFICATIONS, OUTBOX, FOLLOWING, FOLLOWERS,
+    OWNERSHIP_TRANSFERS, PROJECTS_BY_CREATED, PROJECTS_BY_UPDATED, NFTS_BY_CREATED, NFTS_BY_UPDATED,
+    META, TRACKS_BY_PROJECT, NFTS_BY_PROJECT, COLLABORATIONS_BY_PROJECT, PROJECTS_BY_OWNER,
+    NFTS_BY_CREATOR, SEARCH_INDEX, PROJECT_REVISIONS, FORKS, ACTIVITY,
+    COLLABORATIONS_BY_USER, INVITATIONS, INVITATIONS_BY_INVITEE, INVITATIONS_BY_PROJECT,
+    TRACK_STATUS_HISTORY, TRACK_VERSIONS, STEMS, TRACK_DETAILS,
+    NFT_PINS, MAILERS, TRACK_VERSIONS_BY_UPLOADER, STEMS_BY_CONTRIBUTOR, OWNERSHIP_TRANSFERS_BY_PARTY,
+    LINK_CHALLENGES_BY_ACCOUNT, OUTBOX_BY_RECIPIENT,
+};
+use crate::search::{self, doc_key, project_terms, track_terms, nft_terms};
 
 pub fn get_user_by_principal(principal: Principal) -> Option<User> {
     USERS.with(|users| users.borrow().get(&principal))
@@ -13,49 +27,626 @@ pub fn save_user(user: User) {
     });
 }
 
+pub fn remove_user(principal: Principal) {
+    USERS.with(|users| {
+        users.borrow_mut().remove(&principal);
+    });
+}
+
+pub fn get_principal_by_handle(handle: &str) -> Option<Principal> {
+    HANDLES.with(|handles| handles.borrow().get(&handle.to_lowercase()))
+}
+
+pub fn save_handle(handle: &str, principal: Principal) {
+    HANDLES.with(|handles| {
+        handles.borrow_mut().insert(handle.to_lowercase(), principal);
+    });
+}
+
+pub fn remove_handle(handle: &str) {
+    HANDLES.with(|handles| {
+        handles.borrow_mut().remove(&handle.to_lowercase());
+    });
+}
+
+pub fn get_role_by_principal(principal: Principal) -> Option<Role> {
+    ROLES.with(|roles| roles.borrow().get(&principal))
+}
+
+pub fn save_role(principal: Principal, role: Role) {
+    ROLES.with(|roles| {
+        roles.borrow_mut().insert(principal, role);
+    });
+}
+
+pub fn remove_role(principal: Principal) {
+    ROLES.with(|roles| {
+        roles.borrow_mut().remove(&principal);
+    });
+}
+
+pub fn get_all_roles() -> Vec<(Principal, Role)> {
+    ROLES.with(|roles| roles.borrow().iter().collect())
+}
+
+pub fn get_suspension_by_principal(principal: Principal) -> Option<Suspension> {
+    SUSPENSIONS.with(|suspensions| suspensions.borrow().get(&principal))
+}
+
+pub fn save_suspension(suspension: Suspension) {
+    SUSPENSIONS.with(|suspensions| {
+        suspensions.borrow_mut().insert(suspension.principal, suspension);
+    });
+}
+
+pub fn remove_suspension(principal: Principal) {
+    SUSPENSIONS.with(|suspensions| {
+        suspensions.borrow_mut().remove(&principal);
+    });
+}
+
+pub fn get_all_suspensions() -> Vec<Suspension> {
+    SUSPENSIONS.with(|suspensions| {
+        suspensions.borrow().iter().map(|(_, suspension)| suspension).collect()
+    })
+}
+
+pub fn get_linked_principal(principal: Principal) -> Option<LinkedPrincipal> {
+    LINKED_PRINCIPALS.with(|links| links.borrow().get(&principal))
+}
+
+pub fn save_linked_principal(link: LinkedPrincipal) {
+    LINKED_PRINCIPALS.with(|links| {
+        links.borrow_mut().insert(link.principal, link);
+    });
+}
+
+pub fn remove_linked_principal(principal: Principal) {
+    LINKED_PRINCIPALS.with(|links| {
+        links.borrow_mut().remove(&principal);
+    });
+}
+
+pub fn get_linked_principals_for_account(account: Principal) -> Vec<LinkedPrincipal> {
+    LINKED_PRINCIPALS.with(|links| {
+        links.borrow()
+            .iter()
+            .map(|(_, link)| link)
+            .filter(|link| link.account == account)
+            .collect()
+    })
+}
+
+pub fn get_link_challenge(code: &str) -> Option<LinkChallenge> {
+    LINK_CHALLENGES.with(|challenges| challenges.borrow().get(&code.to_string()))
+}
+
+pub fn save_link_challenge(challenge: LinkChallenge) {
+    LINK_CHALLENGES_BY_ACCOUNT.with(|index| {
+        index.borrow_mut().insert(composite_key(&challenge.account.to_text(), &challenge.code), ());
+    });
+
+    LINK_CHALLENGES.with(|challenges| {
+        challenges.borrow_mut().insert(challenge.code.clone(), challenge);
+    });
+}
+
+pub fn remove_link_challenge(code: &str) {
+    if let Some(challenge) = get_link_challenge(code) {
+        LINK_CHALLENGES_BY_ACCOUNT.with(|index| {
+            index.borrow_mut().remove(&composite_key(&challenge.account.to_text(), code));
+        });
+    }
+
+    LINK_CHALLENGES.with(|challenges| {
+        challenges.borrow_mut().remove(&code.to_string());
+    });
+}
+
+/// Number of failed link redemptions in the window that started at or after
+/// `now - window`. An older window counts as empty.
+pub fn get_failed_link_redemptions(now: u64, window: u64) -> u64 {
+    META.with(|meta| {
+        let meta = meta.borrow();
+        let started_at = meta.get(&LINK_FAILURE_WINDOW_KEY.to_string()).unwrap_or(0);
+
+        if started_at.saturating_add(window) <= now {
+            0
+        } else {
+            meta.get(&LINK_FAILURE_COUNT_KEY.to_string()).unwrap_or(0)
+        }
+    })
+}
+
+/// Counts a failed link redemption, starting a new window at `now` when the
+/// current one has passed.
+pub fn record_failed_link_redemption(now: u64, window: u64) {
+    let failures = get_failed_link_redemptions(now, window);
+
+    META.with(|meta| {
+        let mut meta = meta.borrow_mut();
+        if failures == 0 {
+            meta.insert(LINK_FAILURE_WINDOW_KEY.to_string(), now);
+        }
+        meta.insert(LINK_FAILURE_COUNT_KEY.to_string(), failures + 1);
+    });
+}
+
+pub fn get_all_link_challenges() -> Vec<LinkChallenge> {
+    LINK_CHALLENGES.with(|challenges| {
+        challenges.borrow().iter().map(|(_, challenge)| challenge).collect()
+    })
+}
+
+pub fn get_link_challenges_for_account(account: Principal) -> Vec<LinkChallenge> {
+    LINK_CHALLENGES_BY_ACCOUNT.with(|index| prefix_ids(&index.borrow(), &account.to_text()))
+        .iter()
+        .filter_map(|code| get_link_challenge(code))
+        .collect()
+}
+
+pub fn get_delegation(delegate: Principal) -> Option<Delegation> {
+    DELEGATIONS.with(|delegations| delegations.borrow().get(&delegate))
+}
+
+pub fn save_delegation(delegation: Delegation) {
+    DELEGATIONS.with(|delegations| {
+        delegations.borrow_mut().insert(delegation.delegate, delegation);
+    });
+}
+
+pub fn remove_delegation(delegate: Principal) {
+    DELEGATIONS.with(|delegations| {
+        delegations.borrow_mut().remove(&delegate);
+    });
+}
+
+pub fn get_delegations_for_account(account: Principal) -> Vec<Delegation> {
+    DELEGATIONS.with(|delegations| {
+        delegations.borrow()
+            .iter()
+            .map(|(_, delegation)| delegation)
+            .filter(|delegation| delegation.account == account)
+            .collect()
+    })
+}
+
+pub fn get_email_verification(principal: Principal) -> Option<EmailVerification> {
+    EMAIL_VERIFICATIONS.with(|verifications| verifications.borrow().get(&principal))
+}
+
+pub fn save_email_verification(verification: EmailVerification) {
+    EMAIL_VERIFICATIONS.with(|verifications| {
+        verifications.borrow_mut().insert(verification.principal, verification);
+    });
+}
+
+pub fn remove_email_verification(principal: Principal) {
+    EMAIL_VERIFICATIONS.with(|verifications| {
+        verifications.borrow_mut().remove(&principal);
+    });
+}
+
+/// Appends a message to the outbox, assigning the next sequential id. Ids
+/// are never reused, even after the outbox has been drained.
+pub fn push_outbound_email(mut email: OutboundEmail) -> OutboundEmail {
+    email.id = META.with(|meta| {
+        let mut meta = meta.borrow_mut();
+        // Outboxes from before the counter existed continue after their last id
+        let id = meta.get(&OUTBOX_SEQUENCE_KEY.to_string()).unwrap_or_else(|| {
+            OUTBOX.with(|outbox| outbox.borrow().last_key_value().map_or(0, |(id, _)| id + 1))
+        });
+        meta.insert(OUTBOX_SEQUENCE_KEY.to_string(), id + 1);
+        id
+    });
+
+    OUTBOX_BY_RECIPIENT.with(|index| {
+        index.borrow_mut().insert(outbox_recipient_key(&email.to, email.id), ());
+    });
+
+    OUTBOX.with(|outbox| {
+        outbox.borrow_mut().insert(email.id, email.clone());
+    });
+    email
+}
+
+pub fn get_outbound_emails(limit: usize) -> Vec<OutboundEmail> {
+    OUTBOX.with(|outbox| {
+        outbox.borrow().iter().take(limit).map(|(_, email)| email).collect()
+    })
+}
+
+pub fn get_all_outbound_emails() -> Vec<OutboundEmail> {
+    OUTBOX.with(|outbox| {
+        outbox.borrow().iter().map(|(_, email)| email).collect()
+    })
+}
+
+/// Outbox mail addressed to `to`, compared case-insensitively, oldest first.
+pub fn get_outbound_emails_to(to: &str) -> Vec<OutboundEmail> {
+    OUTBOX_BY_RECIPIENT.with(|index| prefix_ids(&index.borrow(), &to.to_ascii_lowercase()))
+        .iter()
+        .filter_map(|id| id.parse().ok())
+        .filter_map(|id| OUTBOX.with(|outbox| outbox.borrow().get(&id)))
+        .collect()
+}
+
+pub fn remove_outbound_email(id: u64) {
+    OUTBOX.with(|outbox| {
+        if let Some(email) = outbox.borrow_mut().remove(&id) {
+            OUTBOX_BY_RECIPIENT.with(|index| {
+                index.borrow_mut().remove(&outbox_recipient_key(&email.to, id));
+            });
+        }
+    });
+}
+
+pub fn is_mailer(principal: Principal) -> bool {
+    MAILERS.with(|mailers| mailers.borrow().contains_key(&principal))
+}
+
+pub fn save_mailer(principal: Principal, added_at: u64) {
+    MAILERS.with(|mailers| {
+        mailers.borrow_mut().insert(principal, added_at);
+    });
+}
+
+pub fn remove_mailer(principal: Principal) {
+    MAILERS.with(|mailers| {
+        mailers.borrow_mut().remove(&principal);
+    });
+}
+
+pub fn get_mailers() -> Vec<Principal> {
+    MAILERS.with(|mailers| mailers.borrow().iter().map(|(principal, _)| principal).collect())
+}
+
+pub fn is_following(follower: Principal, followee: Principal) -> bool {
+    FOLLOWING.with(|following| following.borrow().contains_key(&(follower, followee)))
+}
+
+/// Records the edge in both directions so either side can be listed with a range scan.
+pub fn save_follow(follower: Principal, followee: Principal, followed_at: u64) {
+    FOLLOWING.with(|following| {
+        following.borrow_mut().insert((follower, followee), followed_at);
+    });
+    FOLLOWERS.with(|followers| {
+        followers.borrow_mut().insert((followee, follower), followed_at);
+    });
+}
+
+pub fn remove_follow(follower: Principal, followee: Principal) {
+    FOLLOWING.with(|following| {
+        following.borrow_mut().remove(&(follower, followee));
+    });
+    FOLLOWERS.with(|followers| {
+        followers.borrow_mut().remove(&(followee, follower));
+    });
+}
+
+/// Principals followed by `follower`, in principal order, after `start_after`.
+pub fn get_following_page(follower: Principal, start_after: Option<Principal>, limit: usize) -> Vec<(Principal, u64)> {
+    FOLLOWING.with(|following| principal_edge_page(&following.borrow(), follower, start_after, limit))
+}
+
+/// Principals following `followee`, in principal order, after `start_after`.
+pub fn get_followers_page(followee: Principal, start_after: Option<Principal>, limit: usize) -> Vec<(Principal, u64)> {
+    FOLLOWERS.with(|followers| principal_edge_page(&followers.borrow(), followee, start_after, limit))
+}
+
+fn principal_edge_page(
+    edges: &StableBTreeMap<(Principal, Principal), u64, Memory>,
+    from: Principal,
+    start_after: Option<Principal>,
+    limit: usize,
+) -> Vec<(Principal, u64)> {
+    let start = match start_after {
+        Some(cursor) => Bound::Excluded((from, cursor)),
+        // The empty principal sorts before every other principal
+        None => Bound::Included((from, Principal::from_slice(&[]))),
+    };
+
+    edges
+        .range((start, Bound::Unbounded))
+        .take_while(|((owner, _), _)| *owner == from)
+        .take(limit)
+        .map(|((_, other), followed_at)| (other, followed_at))
+        .collect()
+}
+
 pub fn get_project_by_id(id: &str) -> Option<Project> {
     PROJECTS.with(|projects| projects.borrow().get(id))
 }
 
 pub fn save_project(project: Project) {
+    let previous = get_project_by_id(&project.id);
+    index_project(previous.as_ref(), Some(&project));
+
     PROJECTS.with(|projects| {
         projects.borrow_mut().insert(project.id.clone(), project);
     });
 }
 
+pub fn remove_project(id: &str) {
+    let previous = get_project_by_id(id);
+    index_project(previous.as_ref(), None);
+
+    PROJECTS.with(|projects| {
+        projects.borrow_mut().remove(id);
+    });
+}
+
+fn index_project(previous: Option<&Project>, current: Option<&Project>) {
+    reindex_search(
+        previous.map(|p| (doc_key(SearchKind::Project, &p.id), project_terms(p))),
+        current.map(|p| (doc_key(SearchKind::Project, &p.id), project_terms(p))),
+    );
+    PROJECTS_BY_OWNER.with(|index| {
+        reindex(
+            &mut index.borrow_mut(),
+            previous.map(|p| composite_key(&p.owner.to_text(), &p.id)),
+            current.map(|p| composite_key(&p.owner.to_text(), &p.id)),
+        );
+    });
+    FORKS.with(|index| {
+        reindex(
+            &mut index.borrow_mut(),
+            previous.and_then(|p| p.parent_project_id.as_ref().map(|parent| composite_key(parent, &p.id))),
+            current.and_then(|p| p.parent_project_id.as_ref().map(|parent| composite_key(parent, &p.id))),
+        );
+    });
+    PROJECTS_BY_CREATED.with(|index| {
+        reindex(
+            &mut index.borrow_mut(),
+            previous.map(|p| sort_key(p.created_at, &p.id)),
+            current.map(|p| sort_key(p.created_at, &p.id)),
+        );
+    });
+    PROJECTS_BY_UPDATED.with(|index| {
+        reindex(
+            &mut index.borrow_mut(),
+            previous.map(|p| sort_key(p.updated_at, &p.id)),
+            current.map(|p| sort_key(p.updated_at, &p.id)),
+        );
+    });
+}
+
+/// Pages through projects in `sort` order, resuming after `after` (a key
+/// returned in a previous `ScanPage`).
+pub fn scan_projects<T>(
+    sort: SortField,
+    after: Option<String>,
+    descending: bool,
+    limit: usize,
+    mut select: impl FnMut(Project) -> Option<T>,
+) -> ScanPage<T> {
+    let index = match sort {
+        SortField::CreatedAt => &PROJECTS_BY_CREATED,
+        SortField::UpdatedAt => &PROJECTS_BY_UPDATED,
+    };
+
+    index.with(|index| {
+        scan(&index.borrow(), after, descending, limit, |key, _| {
+            get_project_by_id(id_from_sort_key(key)).and_then(&mut select)
+        })
+    })
+}
+
 pub fn get_all_projects() -> Vec<Project> {
     PROJECTS.with(|projects| {
         projects.borrow().iter().map(|(_, project)| project).collect()
     })
 }
 
+/// Ids of the projects forked directly from `project_id`.
+pub fn get_fork_ids(project_id: &str) -> Vec<String> {
+    FORKS.with(|index| prefix_ids(&index.borrow(), project_id))
+}
+
+pub fn get_projects_by_owner(owner: Principal) -> Vec<Project> {
+    PROJECTS_BY_OWNER.with(|index| prefix_ids(&index.borrow(), &owner.to_text()))
+        .iter()
+        .filter_map(|id| get_project_by_id(id))
+        .collect()
+}
+
+pub fn get_ownership_transfer_by_project(project_id: &str) -> Option<OwnershipTransfer> {
+    OWNERSHIP_TRANSFERS.with(|transfers| transfers.borrow().get(&project_id.to_string()))
+}
+
+pub fn save_ownership_transfer(transfer: OwnershipTransfer) {
+    // A new offer for the same project may name a different recipient
+    remove_ownership_transfer(&transfer.project_id);
+
+    OWNERSHIP_TRANSFERS_BY_PARTY.with(|index| {
+        let mut index = index.borrow_mut();
+        index.insert(composite_key(&transfer.from.to_text(), &transfer.project_id), ());
+        index.insert(composite_key(&transfer.to.to_text(), &transfer.project_id), ());
+    });
+
+    OWNERSHIP_TRANSFERS.with(|transfers| {
+        transfers.borrow_mut().insert(transfer.project_id.clone(), transfer);
+    });
+}
+
+pub fn remove_ownership_transfer(project_id: &str) {
+    OWNERSHIP_TRANSFERS.with(|transfers| {
+        if let Some(transfer) = transfers.borrow_mut().remove(&project_id.to_string()) {
+            OWNERSHIP_TRANSFERS_BY_PARTY.with(|index| {
+                let mut index = index.borrow_mut();
+                index.remove(&composite_key(&transfer.from.to_text(), project_id));
+                index.remove(&composite_key(&transfer.to.to_text(), project_id));
+            });
+        }
+    });
+}
+
+pub fn get_all_ownership_transfers() -> Vec<OwnershipTransfer> {
+    OWNERSHIP_TRANSFERS.with(|transfers| {
+        transfers.borrow().iter().map(|(_, transfer)| transfer).collect()
+    })
+}
+
+/// Pending transfers the principal either offered or was offered.
+pub fn get_ownership_transfers_for_party(principal: Principal) -> Vec<OwnershipTransfer> {
+    OWNERSHIP_TRANSFERS_BY_PARTY.with(|index| prefix_ids(&index.borrow(), &principal.to_text()))
+        .iter()
+        .filter_map(|project_id| get_ownership_transfer_by_project(project_id))
+        .collect()
+}
+
 pub fn get_nft_by_id(id: &str) -> Option<NFT> {
     NFTS.with(|nfts| nfts.borrow().get(id))
 }
 
 pub fn save_nft(nft: NFT) {
+    let previous = get_nft_by_id(&nft.id);
+    index_nft(previous.as_ref(), Some(&nft));
+
     NFTS.with(|nfts| {
         nfts.borrow_mut().insert(nft.id.clone(), nft);
     });
 }
 
+pub fn remove_nft(id: &str) {
+    let previous = get_nft_by_id(id);
+    index_nft(previous.as_ref(), None);
+
+    NFT_PINS.with(|pins| remove_prefix(&mut pins.borrow_mut(), id));
+    NFTS.with(|nfts| {
+        nfts.borrow_mut().remove(id);
+    });
+}
+
+/// The track versions an NFT is pinned to, in track id order.
+pub fn get_nft_pins(nft_id: &str) -> Vec<PinnedTrackVersion> {
+    NFT_PINS.with(|pins| {
+        let prefix = composite_key(nft_id, "");
+        pins.borrow()
+            .range(prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .map(|(key, version)| PinnedTrackVersion {
+                track_id: id_from_composite_key(&key).to_string(),
+                version,
+            })
+            .collect()
+    })
+}
+
+/// Pins `track_id` to `version`, replacing any earlier pin for that track.
+pub fn save_nft_pin(nft_id: &str, track_id: &str, version: u64) {
+    NFT_PINS.with(|pins| {
+        pins.borrow_mut().insert(composite_key(nft_id, track_id), version);
+    });
+}
+
+/// Drops the NFT's pin for `track_id`, returning whether there was one.
+pub fn remove_nft_pin(nft_id: &str, track_id: &str) -> bool {
+    NFT_PINS.with(|pins| pins.borrow_mut().remove(&composite_key(nft_id, track_id)).is_some())
+}
+
+fn index_nft(previous: Option<&NFT>, current: Option<&NFT>) {
+    reindex_search(
+        previous.map(|n| (doc_key(SearchKind::Nft, &n.id), nft_terms(n))),
+        current.map(|n| (doc_key(SearchKind::Nft, &n.id), nft_terms(n))),
+    );
+    NFTS_BY_PROJECT.with(|index| {
+        reindex(
+            &mut index.borrow_mut(),
+            previous.map(|n| composite_key(&n.project_id, &n.id)),
+            current.map(|n| composite_key(&n.project_id, &n.id)),
+        );
+    });
+    NFTS_BY_CREATOR.with(|index| {
+        reindex(
+            &mut index.borrow_mut(),
+            previous.map(|n| composite_key(&n.creator.to_text(), &n.id)),
+            current.map(|n| composite_key(&n.creator.to_text(), &n.id)),
+        );
+    });
+    NFTS_BY_CREATED.with(|index| {
+        reindex(
+            &mut index.borrow_mut(),
+            previous.map(|n| sort_key(n.created_at, &n.id)),
+            current.map(|n| sort_key(n.created_at, &n.id)),
+        );
+    });
+    NFTS_BY_UPDATED.with(|index| {
+        reindex(
+            &mut index.borrow_mut(),
+            previous.map(|n| sort_key(n.updated_at, &n.id)),
+            current.map(|n| sort_key(n.updated_at, &n.id)),
+        );
+    });
+}
+
+/// Pages through NFTs in `sort` order, resuming after `after`.
+pub fn scan_nfts<T>(
+    sort: SortField,
+    after: Option<String>,
+    descending: bool,
+    limit: usize,
+    mut select: impl FnMut(NFT) -> Option<T>,
+) -> ScanPage<T> {
+    let index = match sort {
+        SortField::CreatedAt => &NFTS_BY_CREATED,
+        SortField::UpdatedAt => &NFTS_BY_UPDATED,
+    };
+
+    index.with(|index| {
+        scan(&index.borrow(), after, descending, limit, |key, _| {
+            get_nft_by_id(id_from_sort_key(key)).and_then(&mut select)
+        })
+    })
+}
+
 pub fn get_all_nfts() -> Vec<NFT> {
     NFTS.with(|nfts| {
         nfts.borrow().iter().map(|(_, nft)| nft).collect()
     })
 }
 
+pub fn get_nfts_by_project(project_id: &str) -> Vec<NFT> {
+    NFTS_BY_PROJECT.with(|index| prefix_ids(&index.borrow(), project_id))
+        .iter()
+        .filter_map(|id| get_nft_by_id(id))
+        .collect()
+}
+
+pub fn get_nfts_by_creator(creator: Principal) -> Vec<NFT> {
+    NFTS_BY_CREATOR.with(|index| prefix_ids(&index.borrow(), &creator.to_text()))
+        .iter()
+        .filter_map(|id| get_nft_by_id(id))
+        .collect()
+}
+
 pub fn get_collaboration_by_id(id: &str) -> Option<Collaboration> {
     COLLABORATIONS.with(|collaborations| collaborations.borrow().get(id))
 }
 
 pub fn save_collaboration(collaboration: Collaboration) {
+    COLLABORATIONS_BY_PROJECT.with(|index| {
+        index.borrow_mut().insert(composite_key(&collaboration.project_id, &collaboration.id), ());
+    });
+    COLLABORATIONS_BY_USER.with(|index| {
+        index.borrow_mut().insert(composite_key(&collaboration.user_principal.to_text(), &collaboration.id), ());
+    });
+
     COLLABORATIONS.with(|collaborations| {
         collaborations.borrow_mut().insert(collaboration.id.clone(), collaboration);
     });
 }
 
 pub fn remove_collaboration(id: &str) {
+    if let Some(collaboration) = get_collaboration_by_id(id) {
+        COLLABORATIONS_BY_PROJECT.with(|index| {
+            index.borrow_mut().remove(&composite_key(&collaboration.project_id, id));
+        });
+        COLLABORATIONS_BY_USER.with(|index| {
+            index.borrow_mut().remove(&composite_key(&collaboration.user_principal.to_text(), id));
+        });
+    }
+
     COLLABORATIONS.with(|collaborations| {
         collaborations.borrow_mut().remove(id);
     });
@@ -67,18 +658,794 @@ pub fn get_all_collaborations() -> Vec<Collaboration> {
     })
 }
 
+pub fn get_collaborations_by_project(project_id: &str) -> Vec<Collaboration> {
+    COLLABORATIONS_BY_PROJECT.with(|index| prefix_ids(&index.borrow(), project_id))
+        .iter()
+        .filter_map(|id| get_collaboration_by_id(id))
+        .collect()
+}
+
+pub fn get_collaborations_by_user(principal: Principal) -> Vec<Collaboration> {
+    COLLABORATIONS_BY_USER.with(|index| prefix_ids(&index.borrow(), &principal.to_text()))
+        .iter()
+        .filter_map(|id| get_collaboration_by_id(id))
+        .collect()
+}
+
+/// Pages through a project's collaborations in id order, resuming after `after`.
+pub fn scan_project_collaborations(
+    project_id: &str,
+    after: Option<String>,
+    limit: usize,
+) -> ScanPage<Collaboration> {
+    COLLABORATIONS_BY_PROJECT.with(|index| {
+        scan_prefix(&index.borrow(), project_id, after, false, limit, |key, _| {
+            get_collaboration_by_id(id_from_composite_key(key))
+        })
+    })
+}
+
+pub fn get_invitation_by_id(id: &str) -> Option<Invitation> {
+    INVITATIONS.with(|invitations| invitations.borrow().get(&id.to_string()))
+}
+
+pub fn save_invitation(invitation: Invitation) {
+    INVITATIONS_BY_INVITEE.with(|index| {
+        index.borrow_mut().insert(composite_key(&invitation.invitee.to_text(), &invitation.id), ());
+    });
+    INVITATIONS_BY_PROJECT.with(|index| {
+        index.borrow_mut().insert(composite_key(&invitation.project_id, &invitation.id), ());
+    });
+
+    INVITATIONS.with(|invitations| {
+        invitations.borrow_mut().insert(invitation.id.clone(), invitation);
+    });
+}
+
+pub fn remove_invitation(id: &str) {
+    if let Some(invitation) = get_invitation_by_id(id) {
+        INVITATIONS_BY_INVITEE.with(|index| {
+            index.borrow_mut().remove(&composite_key(&invitation.invitee.to_text(), id));
+        });
+        INVITATIONS_BY_PROJECT.with(|index| {
+            index.borrow_mut().remove(&composite_key(&invitation.project_id, id));
+        });
+    }
+
+    INVITATIONS.with(|invitations| {
+        invitations.borrow_mut().remove(&id.to_string());
+    });
+}
+
+pub fn get_invitations_by_invitee(invitee: Principal) -> Vec<Invitation> {
+    INVITATIONS_BY_INVITEE.with(|index| prefix_ids(&index.borrow(), &invitee.to_text()))
+        .iter()
+        .filter_map(|id| get_invitation_by_id(id))
+        .collect()
+}
+
+pub fn get_invitations_by_project(project_id: &str) -> Vec<Invitation> {
+    INVITATIONS_BY_PROJECT.with(|index| prefix_ids(&index.borrow(), project_id))
+        .iter()
+        .filter_map(|id| get_invitation_by_id(id))
+        .collect()
+}
+
+pub fn get_all_invitations() -> Vec<Invitation> {
+    INVITATIONS.with(|invitations| {
+        invitations.borrow().iter().map(|(_, invitation)| invitation).collect()
+    })
+}
+
 pub fn get_track_by_id(id: &str) -> Option<Track> {
     TRACKS.with(|tracks| tracks.borrow().get(id))
 }
 
 pub fn save_track(track: Track) {
+    let previous = get_track_by_id(&track.id);
+    reindex_search(
+        previous.map(|t| (doc_key(SearchKind::Track, &t.id), track_terms(&t))),
+        Some((doc_key(SearchKind::Track, &track.id), track_terms(&track))),
+    );
+
+    TRACKS_BY_PROJECT.with(|index| {
+        index.borrow_mut().insert(composite_key(&track.project_id, &track.id), ());
+    });
+
     TRACKS.with(|tracks| {
         tracks.borrow_mut().insert(track.id.clone(), track);
     });
 }
 
+pub fn remove_track(id: &str) {
+    if let Some(track) = get_track_by_id(id) {
+        reindex_search(Some((doc_key(SearchKind::Track, id), track_terms(&track))), None);
+        TRACKS_BY_PROJECT.with(|index| {
+            index.borrow_mut().remove(&composite_key(&track.project_id, id));
+        });
+    }
+
+    TRACK_STATUS_HISTORY.with(|history| remove_prefix(&mut history.borrow_mut(), id));
+    for version in get_track_versions(id) {
+        TRACK_VERSIONS_BY_UPLOADER.with(|index| {
+            index.borrow_mut().remove(&uploader_key(&version));
+        });
+    }
+    for stem in get_track_stems(id) {
+        STEMS_BY_CONTRIBUTOR.with(|index| {
+            index.borrow_mut().remove(&contributor_key(&stem));
+        });
+    }
+    TRACK_VERSIONS.with(|versions| remove_prefix(&mut versions.borrow_mut(), id));
+    STEMS.with(|stems| remove_prefix(&mut stems.borrow_mut(), id));
+    remove_track_details(id);
+
+    TRACKS.with(|tracks| {
+        tracks.borrow_mut().remove(id);
+    });
+}
+
+pub fn get_track_version(track_id: &str, number: u64) -> Option<TrackVersion> {
+    TRACK_VERSIONS.with(|versions| versions.borrow().get(&track_version_key(track_id, number)))
+}
+
+/// Number of versions a track has. Versions are numbered from 1 without
+/// gaps, so this is the latest number.
+pub fn count_track_versions(track_id: &str) -> u64 {
+    TRACK_VERSIONS.with(|versions| {
+        scan_prefix(&versions.borrow(), track_id, None, true, 1, |_, last: TrackVersion| Some(last.number))
+            .items
+            .pop()
+            .unwrap_or(0)
+    })
+}
+
+/// Stores a new version, assigning the next number for its track.
+pub fn push_track_version(mut version: TrackVersion) -> TrackVersion {
+    version.number = count_track_versions(&version.track_id) + 1;
+
+    TRACK_VERSIONS_BY_UPLOADER.with(|index| {
+        index.borrow_mut().insert(uploader_key(&version), ());
+    });
+
+    TRACK_VERSIONS.with(|versions| {
+        versions
+            .borrow_mut()
+            .insert(track_version_key(&version.track_id, version.number), version.clone());
+    });
+    version
+}
+
+/// A track's versions, oldest first.
+pub fn get_track_versions(track_id: &str) -> Vec<TrackVersion> {
+    TRACK_VERSIONS.with(|versions| {
+        let prefix = composite_key(track_id, "");
+        versions
+            .borrow()
+            .range(prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .map(|(_, version)| version)
+            .collect()
+    })
+}
+
+pub fn get_all_track_versions() -> Vec<TrackVersion> {
+    TRACK_VERSIONS.with(|versions| {
+        versions.borrow().iter().map(|(_, version)| version).collect()
+    })
+}
+
+/// Versions the principal uploaded, ordered by track id and number.
+pub fn get_track_versions_by_uploader(principal: Principal) -> Vec<TrackVersion> {
+    TRACK_VERSIONS_BY_UPLOADER.with(|index| prefix_ids(&index.borrow(), &principal.to_text()))
+        .iter()
+        .filter_map(|key| TRACK_VERSIONS.with(|versions| versions.borrow().get(key)))
+        .collect()
+}
+
+pub fn get_track_details(track_id: &str) -> Option<TrackDetails> {
+    TRACK_DETAILS.with(|details| details.borrow().get(&track_id.to_string()))
+}
+
+pub fn save_track_details(details: TrackDetails) {
+    TRACK_DETAILS.with(|map| {
+        map.borrow_mut().insert(details.track_id.clone(), details);
+    });
+}
+
+pub fn remove_track_details(track_id: &str) {
+    TRACK_DETAILS.with(|details| {
+        details.borrow_mut().remove(&track_id.to_string());
+    });
+}
+
+pub fn get_stem(track_id: &str, stem_id: &str) -> Option<Stem> {
+    STEMS.with(|stems| stems.borrow().get(&composite_key(track_id, stem_id)))
+}
+
+pub fn save_stem(stem: Stem) {
+    let previous = get_stem(&stem.track_id, &stem.id).map(|previous| contributor_key(&previous));
+    STEMS_BY_CONTRIBUTOR.with(|index| reindex(&mut index.borrow_mut(), previous, Some(contributor_key(&stem))));
+
+    STEMS.with(|stems| {
+        stems.borrow_mut().insert(composite_key(&stem.track_id, &stem.id), stem);
+    });
+}
+
+pub fn remove_stem(track_id: &str, stem_id: &str) {
+    STEMS.with(|stems| {
+        if let Some(stem) = stems.borrow_mut().remove(&composite_key(track_id, stem_id)) {
+            STEMS_BY_CONTRIBUTOR.with(|index| {
+                index.borrow_mut().remove(&contributor_key(&stem));
+            });
+        }
+    });
+}
+
+/// A track's stems, ordered by id.
+pub fn get_track_stems(track_id: &str) -> Vec<Stem> {
+    STEMS.with(|stems| {
+        let prefix = composite_key(track_id, "");
+        stems
+            .borrow()
+            .range(prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .map(|(_, stem)| stem)
+            .collect()
+    })
+}
+
+pub fn get_all_stems() -> Vec<Stem> {
+    STEMS.with(|stems| {
+        stems.borrow().iter().map(|(_, stem)| stem).collect()
+    })
+}
+
+/// Stems the principal contributed, ordered by track id and stem id.
+pub fn get_stems_by_contributor(principal: Principal) -> Vec<Stem> {
+    STEMS_BY_CONTRIBUTOR.with(|index| prefix_ids(&index.borrow(), &principal.to_text()))
+        .iter()
+        .filter_map(|key| STEMS.with(|stems| stems.borrow().get(key)))
+        .collect()
+}
+
+/// Records each track's current content as version 1 if it has no
+/// versions yet. Uploads are credited to the project owner.
+pub fn backfill_track_versions() {
+    for mut track in get_all_tracks() {
+        if track.active_version.is_some() {
+            continue;
+        }
+
+        let uploaded_by = get_project_by_id(&track.project_id)
+            .map_or(Principal::anonymous(), |project| project.owner);
+
+        let version = push_track_version(TrackVersion {
+            track_id: track.id.clone(),
+            number: 0,
+            ipfs_hash: track.ipfs_hash.clone(),
+            duration: track.duration,
+            uploaded_by,
+            notes: None,
+            created_at: track.created_at,
+        });
+
+        track.active_version = Some(version.number);
+        save_track(track);
+    }
+}
+
+/// Appends to a track's status history, assigning the next sequence number.
+pub fn push_track_status_change(mut change: TrackStatusChange) -> TrackStatusChange {
+    TRACK_STATUS_HISTORY.with(|history| {
+        let mut history = history.borrow_mut();
+
+        change.sequence = scan_prefix(&history, &change.track_id, None, true, 1, |_, last: TrackStatusChange| {
+            Some(last.sequence + 1)
+        })
+        .items
+        .pop()
+        .unwrap_or(1);
+
+        let key = composite_key(&change.track_id, &format!("{:020}", change.sequence));
+        history.insert(key, change.clone());
+    });
+    change
+}
+
+/// A track's status changes, oldest first.
+pub fn get_track_status_history(track_id: &str) -> Vec<TrackStatusChange> {
+    TRACK_STATUS_HISTORY.with(|history| {
+        let prefix = composite_key(track_id, "");
+        history
+            .borrow()
+            .range(prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .map(|(_, change)| change)
+            .collect()
+    })
+}
+
 pub fn get_all_tracks() -> Vec<Track> {
     TRACKS.with(|tracks| {
         tracks.borrow().iter().map(|(_, track)| track).collect()
     })
+}
+
+pub fn get_tracks_by_project(project_id: &str) -> Vec<Track> {
+    TRACKS_BY_PROJECT.with(|index| prefix_ids(&index.borrow(), project_id))
+        .iter()
+        .filter_map(|id| get_track_by_id(id))
+        .collect()
+}
+
+/// Pages through a project's tracks in id order, resuming after `after`.
+pub fn scan_project_tracks<T>(
+    project_id: &str,
+    after: Option<String>,
+    limit: usize,
+    mut select: impl FnMut(Track) -> Option<T>,
+) -> ScanPage<T> {
+    TRACKS_BY_PROJECT.with(|index| {
+        scan_prefix(&index.borrow(), project_id, after, false, limit, |key, _| {
+            get_track_by_id(id_from_composite_key(key)).and_then(&mut select)
+        })
+    })
+}
+
+pub fn get_schema_version() -> u64 {
+    META.with(|meta| meta.borrow().get(&SCHEMA_VERSION_KEY.to_string()).unwrap_or(0))
+}
+
+pub fn set_schema_version(version: u64) {
+    META.with(|meta| {
+        meta.borrow_mut().insert(SCHEMA_VERSION_KEY.to_string(), version);
+    });
+}
+
+/// Moves users out of the map created with their original 1 KiB bound.
+pub fn move_legacy_users() {
+    LEGACY_USERS.with(|legacy| USERS.with(|users| move_entries(&mut legacy.borrow_mut(), &mut users.borrow_mut())));
+}
+
+/// Moves projects out of the map created with their original 2 KiB bound.
+pub fn move_legacy_projects() {
+    LEGACY_PROJECTS.with(|legacy| {
+        PROJECTS.with(|projects| move_entries(&mut legacy.borrow_mut(), &mut projects.borrow_mut()))
+    });
+}
+
+/// Moves NFTs out of the map created with their original 2 KiB bound.
+pub fn move_legacy_nfts() {
+    LEGACY_NFTS.with(|legacy| NFTS.with(|nfts| move_entries(&mut legacy.borrow_mut(), &mut nfts.borrow_mut())));
+}
+
+/// Rebuilds the created/updated sort indexes from the primary maps.
+pub fn rebuild_sort_indexes() {
+    for index in [&PROJECTS_BY_CREATED, &PROJECTS_BY_UPDATED, &NFTS_BY_CREATED, &NFTS_BY_UPDATED] {
+        index.with(|index| clear(&mut index.borrow_mut()));
+    }
+
+    for project in get_all_projects() {
+        index_project(None, Some(&project));
+    }
+
+    for nft in get_all_nfts() {
+        index_nft(None, Some(&nft));
+    }
+}
+
+/// Rebuilds the project-scoped and owner/creator indexes from the primary maps.
+pub fn rebuild_entity_indexes() {
+    for index in [
+        &TRACKS_BY_PROJECT,
+        &NFTS_BY_PROJECT,
+        &COLLABORATIONS_BY_PROJECT,
+        &PROJECTS_BY_OWNER,
+        &NFTS_BY_CREATOR,
+        &COLLABORATIONS_BY_USER,
+    ] {
+        index.with(|index| clear(&mut index.borrow_mut()));
+    }
+
+    for project in get_all_projects() {
+        PROJECTS_BY_OWNER.with(|index| {
+            index.borrow_mut().insert(composite_key(&project.owner.to_text(), &project.id), ());
+        });
+    }
+
+    for nft in get_all_nfts() {
+        NFTS_BY_PROJECT.with(|index| {
+            index.borrow_mut().insert(composite_key(&nft.project_id, &nft.id), ());
+        });
+        NFTS_BY_CREATOR.with(|index| {
+            index.borrow_mut().insert(composite_key(&nft.creator.to_text(), &nft.id), ());
+        });
+    }
+
+    for track in get_all_tracks() {
+        TRACKS_BY_PROJECT.with(|index| {
+            index.borrow_mut().insert(composite_key(&track.project_id, &track.id), ());
+        });
+    }
+
+    for collaboration in get_all_collaborations() {
+        COLLABORATIONS_BY_PROJECT.with(|index| {
+            index.borrow_mut().insert(composite_key(&collaboration.project_id, &collaboration.id), ());
+        });
+        COLLABORATIONS_BY_USER.with(|index| {
+            index.borrow_mut().insert(composite_key(&collaboration.user_principal.to_text(), &collaboration.id), ());
+        });
+    }
+}
+
+/// Rebuilds the indexes that find an account's versions, stems, transfers,
+/// link codes and mail without scanning the primary maps.
+pub fn rebuild_account_indexes() {
+    for index in [
+        &TRACK_VERSIONS_BY_UPLOADER,
+        &STEMS_BY_CONTRIBUTOR,
+        &OWNERSHIP_TRANSFERS_BY_PARTY,
+        &LINK_CHALLENGES_BY_ACCOUNT,
+        &OUTBOX_BY_RECIPIENT,
+    ] {
+        index.with(|index| clear(&mut index.borrow_mut()));
+    }
+
+    for version in get_all_track_versions() {
+        TRACK_VERSIONS_BY_UPLOADER.with(|index| {
+            index.borrow_mut().insert(uploader_key(&version), ());
+        });
+    }
+
+    for stem in get_all_stems() {
+        STEMS_BY_CONTRIBUTOR.with(|index| {
+            index.borrow_mut().insert(contributor_key(&stem), ());
+        });
+    }
+
+    for transfer in get_all_ownership_transfers() {
+        OWNERSHIP_TRANSFERS_BY_PARTY.with(|index| {
+            let mut index = index.borrow_mut();
+            index.insert(composite_key(&transfer.from.to_text(), &transfer.project_id), ());
+            index.insert(composite_key(&transfer.to.to_text(), &transfer.project_id), ());
+        });
+    }
+
+    for challenge in get_all_link_challenges() {
+        LINK_CHALLENGES_BY_ACCOUNT.with(|index| {
+            index.borrow_mut().insert(composite_key(&challenge.account.to_text(), &challenge.code), ());
+        });
+    }
+
+    for email in get_all_outbound_emails() {
+        OUTBOX_BY_RECIPIENT.with(|index| {
+            index.borrow_mut().insert(outbox_recipient_key(&email.to, email.id), ());
+        });
+    }
+}
+
+pub fn get_project_revision(project_id: &str, number: u64) -> Option<ProjectRevision> {
+    PROJECT_REVISIONS.with(|revisions| revisions.borrow().get(&revision_key(project_id, number)))
+}
+
+pub fn get_latest_project_revision(project_id: &str) -> Option<ProjectRevision> {
+    PROJECT_REVISIONS.with(|revisions| {
+        scan_prefix(&revisions.borrow(), project_id, None, true, 1, |_, revision| Some(revision))
+            .items
+            .pop()
+    })
+}
+
+pub fn save_project_revision(revision: ProjectRevision) {
+    PROJECT_REVISIONS.with(|revisions| {
+        revisions
+            .borrow_mut()
+            .insert(revision_key(&revision.project_id, revision.number), revision);
+    });
+}
+
+pub fn remove_project_revisions(project_id: &str) {
+    PROJECT_REVISIONS.with(|revisions| remove_prefix(&mut revisions.borrow_mut(), project_id));
+}
+
+/// Pages through a project's revisions, newest first.
+pub fn scan_project_revisions(
+    project_id: &str,
+    after: Option<String>,
+    limit: usize,
+) -> ScanPage<ProjectRevision> {
+    PROJECT_REVISIONS.with(|revisions| {
+        scan_prefix(&revisions.borrow(), project_id, after, true, limit, |_, revision| Some(revision))
+    })
+}
+
+/// Appends an event to its project's log, assigning the next global id.
+pub fn push_activity_event(mut event: ActivityEvent) -> ActivityEvent {
+    event.id = META.with(|meta| {
+        let mut meta = meta.borrow_mut();
+        let id = meta.get(&ACTIVITY_SEQUENCE_KEY.to_string()).unwrap_or(0);
+        meta.insert(ACTIVITY_SEQUENCE_KEY.to_string(), id + 1);
+        id
+    });
+
+    ACTIVITY.with(|activity| {
+        activity
+            .borrow_mut()
+            .insert(activity_key(&event.project_id, event.id), event.clone());
+    });
+    event
+}
+
+/// Pages through a project's activity, newest first.
+pub fn scan_project_activity(
+    project_id: &str,
+    after: Option<String>,
+    limit: usize,
+) -> ScanPage<ActivityEvent> {
+    ACTIVITY.with(|activity| {
+        scan_prefix(&activity.borrow(), project_id, after, true, limit, |_, event| Some(event))
+    })
+}
+
+/// Up to `limit` of a project's newest events with ids below `before_id`.
+pub fn get_project_activity_before(
+    project_id: &str,
+    before_id: Option<u64>,
+    limit: usize,
+) -> Vec<ActivityEvent> {
+    let after = before_id.map(|id| activity_key(project_id, id));
+    scan_project_activity(project_id, after, limit).items
+}
+
+pub fn remove_project_activity(project_id: &str) {
+    ACTIVITY.with(|activity| remove_prefix(&mut activity.borrow_mut(), project_id));
+}
+
+/// Postings for every indexed token starting with `prefix`, as
+/// (token, document key, weight). Stops after `limit` postings.
+pub fn search_postings(prefix: &str, limit: usize) -> Vec<(String, String, u32)> {
+    SEARCH_INDEX.with(|index| {
+        index
+            .borrow()
+            .range(prefix.to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .take(limit)
+            .filter_map(|(key, weight)| {
+                let (token, doc) = key.split_once(':')?;
+                Some((token.to_string(), doc.to_string(), weight))
+            })
+            .collect()
+    })
+}
+
+/// Re-tokenizes every project, track and NFT into a fresh search index.
+pub fn rebuild_search_index() {
+    SEARCH_INDEX.with(|index| clear(&mut index.borrow_mut()));
+
+    for project in get_all_projects() {
+        reindex_search(None, Some((doc_key(SearchKind::Project, &project.id), project_terms(&project))));
+    }
+
+    for track in get_all_tracks() {
+        reindex_search(None, Some((doc_key(SearchKind::Track, &track.id), track_terms(&track))));
+    }
+
+    for nft in get_all_nfts() {
+        reindex_search(None, Some((doc_key(SearchKind::Nft, &nft.id), nft_terms(&nft))));
+    }
+}
+
+/// Swaps a document's postings, given as (document key, weighted terms).
+fn reindex_search(
+    previous: Option<(String, Vec<(String, u32)>)>,
+    current: Option<(String, Vec<(String, u32)>)>,
+) {
+    SEARCH_INDEX.with(|index| {
+        let mut index = index.borrow_mut();
+
+        if let Some((doc, terms)) = previous {
+            for (term, _) in terms {
+                index.remove(&search::posting_key(&term, &doc));
+            }
+        }
+
+        if let Some((doc, terms)) = current {
+            for (term, weight) in terms {
+                index.insert(search::posting_key(&term, &doc), weight);
+            }
+        }
+    });
+}
+
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+const ACTIVITY_SEQUENCE_KEY: &str = "activity_sequence";
+const OUTBOX_SEQUENCE_KEY: &str = "outbox_sequence";
+const LINK_FAILURE_WINDOW_KEY: &str = "link_failure_window";
+const LINK_FAILURE_COUNT_KEY: &str = "link_failure_count";
+
+/// Upper bound on entries visited per page, so sparse filters can't exhaust
+/// the instruction limit. A page may come back short with a cursor when hit.
+const MAX_SCAN: usize = 2_000;
+
+/// Result of a bounded scan. `last_key` is set when more entries may follow
+/// and is where the next scan should resume.
+pub struct ScanPage<T> {
+    pub items: Vec<T>,
+    pub last_key: Option<String>,
+}
+
+/// Index keys sort by timestamp first; zero-padding keeps string order numeric.
+fn sort_key(timestamp: u64, id: &str) -> String {
+    format!("{:020}:{}", timestamp, id)
+}
+
+fn id_from_sort_key(key: &str) -> &str {
+    key.split_once(':').map_or(key, |(_, id)| id)
+}
+
+/// Keys for "parent -> child" indexes. Neither ids nor principal text
+/// contain ':', so a prefix scan on "parent:" only sees that parent's children.
+fn composite_key(parent: &str, child: &str) -> String {
+    format!("{}:{}", parent, child)
+}
+
+fn revision_key(project_id: &str, number: u64) -> String {
+    composite_key(project_id, &format!("{:020}", number))
+}
+
+fn track_version_key(track_id: &str, number: u64) -> String {
+    composite_key(track_id, &format!("{:020}", number))
+}
+
+fn activity_key(project_id: &str, id: u64) -> String {
+    composite_key(project_id, &format!("{:020}", id))
+}
+
+fn uploader_key(version: &TrackVersion) -> String {
+    composite_key(&version.uploaded_by.to_text(), &track_version_key(&version.track_id, version.number))
+}
+
+fn contributor_key(stem: &Stem) -> String {
+    composite_key(&stem.contributor.to_text(), &composite_key(&stem.track_id, &stem.id))
+}
+
+fn outbox_recipient_key(to: &str, id: u64) -> String {
+    composite_key(&to.to_ascii_lowercase(), &format!("{:020}", id))
+}
+
+fn id_from_composite_key(key: &str) -> &str {
+    key.rsplit_once(':').map_or(key, |(_, id)| id)
+}
+
+/// Everything after `parent:` in each key under `parent` in a composite-key
+/// index; the child may itself be a composite key.
+fn prefix_ids(index: &StableBTreeMap<String, (), Memory>, parent: &str) -> Vec<String> {
+    let prefix = composite_key(parent, "");
+
+    index
+        .range(prefix.clone()..)
+        .take_while(|(key, _)| key.starts_with(&prefix))
+        .map(|(key, _)| key[prefix.len()..].to_string())
+        .collect()
+}
+
+/// Removes every key under `parent` from a composite-key map.
+fn remove_prefix<V: Storable>(map: &mut StableBTreeMap<String, V, Memory>, parent: &str) {
+    let prefix = composite_key(parent, "");
+    let keys: Vec<String> = map
+        .range(prefix.clone()..)
+        .take_while(|(key, _)| key.starts_with(&prefix))
+        .map(|(key, _)| key)
+        .collect();
+
+    for key in keys {
+        map.remove(&key);
+    }
+}
+
+/// Like `scan`, but restricted to the keys under `parent`.
+fn scan_prefix<V: Storable, T>(
+    map: &StableBTreeMap<String, V, Memory>,
+    parent: &str,
+    after: Option<String>,
+    descending: bool,
+    limit: usize,
+    select: impl FnMut(&str, V) -> Option<T>,
+) -> ScanPage<T> {
+    let prefix = composite_key(parent, "");
+    // ';' sorts right after ':', so this bounds every key under the prefix
+    let end = format!("{};", parent);
+
+    // Cursors from another parent would start the scan in the wrong place
+    let after = after.filter(|key| key.starts_with(&prefix));
+
+    let entries: Box<dyn Iterator<Item = (String, V)>> = match (descending, after) {
+        (false, Some(key)) => Box::new(map.range((Bound::Excluded(key), Bound::Excluded(end)))),
+        (false, None) => Box::new(map.range((Bound::Included(prefix), Bound::Excluded(end)))),
+        (true, Some(key)) => Box::new(map.range((Bound::Included(prefix), Bound::Excluded(key))).rev()),
+        (true, None) => Box::new(map.range((Bound::Included(prefix), Bound::Excluded(end))).rev()),
+    };
+
+    collect_page(entries, limit, select)
+}
+
+fn reindex(index: &mut StableBTreeMap<String, (), Memory>, previous: Option<String>, current: Option<String>) {
+    if previous == current {
+        return;
+    }
+
+    if let Some(key) = previous {
+        index.remove(&key);
+    }
+
+    if let Some(key) = current {
+        index.insert(key, ());
+    }
+}
+
+/// Drains `from` into `to`, for maps moved to a new memory.
+fn move_entries<K: Storable + Ord + Clone, V: Storable, const MAX_SIZE: u32>(
+    from: &mut StableBTreeMap<K, Legacy<V, MAX_SIZE>, Memory>,
+    to: &mut StableBTreeMap<K, V, Memory>,
+) {
+    let keys: Vec<K> = from.iter().map(|(key, _)| key).collect();
+
+    for key in keys {
+        if let Some(Legacy(value)) = from.remove(&key) {
+            to.insert(key, value);
+        }
+    }
+}
+
+fn clear<V: Storable>(map: &mut StableBTreeMap<String, V, Memory>) {
+    let keys: Vec<String> = map.iter().map(|(key, _)| key).collect();
+    for key in keys {
+        map.remove(&key);
+    }
+}
+
+fn scan<V: Storable, T>(
+    map: &StableBTreeMap<String, V, Memory>,
+    after: Option<String>,
+    descending: bool,
+    limit: usize,
+    select: impl FnMut(&str, V) -> Option<T>,
+) -> ScanPage<T> {
+    let after = after.map_or(Bound::Unbounded, Bound::Excluded);
+    let entries: Box<dyn Iterator<Item = (String, V)>> = if descending {
+        Box::new(map.range((Bound::Unbounded, after)).rev())
+    } else {
+        Box::new(map.range((after, Bound::Unbounded)))
+    };
+
+    collect_page(entries, limit, select)
+}
+
+/// Runs `select` over `entries` until `limit` items or `MAX_SCAN` entries.
+fn collect_page<V, T>(
+    entries: impl Iterator<Item = (String, V)>,
+    limit: usize,
+    mut select: impl FnMut(&str, V) -> Option<T>,
+) -> ScanPage<T> {
+    let mut items = Vec::new();
+    let mut last_key = None;
+    let mut exhausted = true;
+
+    for (scanned, (key, value)) in entries.enumerate() {
+        if let Some(item) = select(&key, value) {
+            items.push(item);
+        }
+        last_key = Some(key);
+
+        if items.len() >= limit || scanned + 1 >= MAX_SCAN {
+            exhausted = false;
+            break;
+        }
+    }
+
+    ScanPage {
+        items,
+        last_key: if exhausted { None } else { last_key },
+    }
 }
\ No newline at end of file
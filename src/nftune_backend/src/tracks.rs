@@ -0,0 +1,664 @@
+use candid::Principal;
+use ic_cdk::api::time;
+use uuid::Uuid;
+use crate::types::{
+    ActivityKind, Project, RevisionChange, Scope, Stem, StemRole, Track, TrackCredit, TrackDetails,
+    TrackMetadata, TrackStatus, TrackStatusChange, TrackVersion, TrackVersionComparison,
+};
+use crate::storage::{
+    self, get_track_by_id, get_project_by_id, save_track, remove_track, push_track_status_change,
+    get_nfts_by_project, get_track_version, push_track_version, get_track_versions as stored_versions,
+    count_track_versions, remove_nft_pin, save_nft,
+    get_stem, save_stem, save_track_details,
+};
+use crate::auth::{require_scope, caller_principal};
+use crate::activity;
+use crate::history::save_project_with_history;
+use crate::validation;
+use crate::projects::{ensure_not_archived, get_visible_project, is_project_member, is_project_owner};
+
+/// Who may make a status transition.
+enum Permission {
+    Member,
+    Owner,
+}
+
+/// Tracks move forward one stage at a time and can step back one stage.
+/// Marking a track Completed, or reopening it, is up to the project owner.
+fn transition_permission(from: &TrackStatus, to: &TrackStatus) -> Option<Permission> {
+    use TrackStatus::*;
+
+    match (from, to) {
+        (Draft, Recording) | (Recording, Mixing) => Some(Permission::Member),
+        (Recording, Draft) | (Mixing, Recording) => Some(Permission::Member),
+        (Mixing, Completed) | (Completed, Mixing) => Some(Permission::Owner),
+        _ => None,
+    }
+}
+
+pub fn update_track_status(track_id: String, status: TrackStatus) -> Result<Track, String> {
+    let actor = require_scope(Scope::TracksWrite)?;
+    let caller = actor.account;
+
+    let mut track = get_track_by_id(&track_id)
+        .ok_or_else(|| "Track not found".to_string())?;
+
+    let project = get_track_project(&track)?;
+
+    if !is_project_member(&project, caller) {
+        return Err("Only project owner or collaborators can update tracks".to_string());
+    }
+
+    ensure_not_archived(&project)?;
+
+    if track.status == status {
+        return Ok(track);
+    }
+
+    match transition_permission(&track.status, &status) {
+        None => {
+            return Err(format!("Cannot move a track from {:?} to {:?}", track.status, status));
+        }
+        Some(Permission::Owner) if !is_project_owner(&project, caller) => {
+            return Err(format!(
+                "Only project owner can move a track from {:?} to {:?}",
+                track.status, status
+            ));
+        }
+        Some(_) => {}
+    }
+
+    record_status_change(&track, status.clone(), caller);
+    track.status = status.clone();
+    save_track(track.clone());
+    activity::record(&project.id, caller, ActivityKind::TrackStatusChanged { track_id, status });
+
+    Ok(track)
+}
+
+const MAX_VERSIONS_PER_TRACK: u64 = 100;
+const MAX_STEMS_PER_TRACK: usize = 32;
+
+/// Renames a track or points it at new audio. `None` leaves a field as is;
+/// new audio is stored as a new active version. Completed tracks are locked
+/// until the owner reopens them.
+pub fn update_track(
+    track_id: String,
+    name: Option<String>,
+    ipfs_hash: Option<String>,
+    duration: Option<u64>,
+) -> Result<Track, String> {
+    let actor = require_scope(Scope::TracksWrite)?;
+    let caller = actor.account;
+
+    let mut track = get_track_by_id(&track_id)
+        .ok_or_else(|| "Track not found".to_string())?;
+
+    let project = get_track_project(&track)?;
+
+    if !is_project_member(&project, caller) {
+        return Err("Only project owner or collaborators can update tracks".to_string());
+    }
+
+    ensure_not_archived(&project)?;
+    ensure_editable(&track)?;
+
+    if let Some(new_name) = name {
+        validation::validate_name(&new_name)?;
+        track.name = new_name.trim().to_string();
+    }
+
+    if let Some(ipfs_hash) = &ipfs_hash {
+        validation::validate_cid(ipfs_hash)?;
+    }
+
+    if ipfs_hash.is_some() || duration.is_some() {
+        let version = add_version(
+            &track,
+            ipfs_hash.unwrap_or_else(|| track.ipfs_hash.clone()),
+            duration.unwrap_or(track.duration),
+            None,
+            caller,
+        )?;
+        activate(&mut track, &version);
+        activity::record(
+            &project.id,
+            caller,
+            ActivityKind::TrackVersionAdded { track_id: track_id.clone(), version: version.number },
+        );
+    }
+
+    save_track(track.clone());
+    activity::record(&project.id, caller, ActivityKind::TrackUpdated { track_id });
+
+    Ok(track)
+}
+
+/// Replaces a track's descriptive metadata. Text fields are trimmed and
+/// blank ones cleared; ISRCs may be given with or without hyphens.
+pub fn update_track_metadata(track_id: String, metadata: TrackMetadata) -> Result<Track, String> {
+    let actor = require_scope(Scope::TracksWrite)?;
+    let caller = actor.account;
+
+    let mut track = get_track_by_id(&track_id)
+        .ok_or_else(|| "Track not found".to_string())?;
+
+    let project = get_track_project(&track)?;
+
+    if !is_project_member(&project, caller) {
+        return Err("Only project owner or collaborators can update tracks".to_string());
+    }
+
+    ensure_not_archived(&project)?;
+    ensure_editable(&track)?;
+
+    let metadata = normalize_metadata(metadata);
+    validation::validate_track_metadata(&metadata)?;
+
+    track.metadata = metadata;
+    save_track(track.clone());
+    activity::record(&project.id, caller, ActivityKind::TrackUpdated { track_id });
+
+    Ok(track)
+}
+
+fn normalize_metadata(metadata: TrackMetadata) -> TrackMetadata {
+    let text = |value: Option<String>| {
+        value
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    TrackMetadata {
+        key: text(metadata.key),
+        time_signature: text(metadata.time_signature).map(|value| value.replace(' ', "")),
+        genre: text(metadata.genre).map(|value| value.to_lowercase()),
+        isrc: text(metadata.isrc).map(|value| value.replace('-', "").to_uppercase()),
+        language: text(metadata.language).map(|value| value.to_lowercase()),
+        ..metadata
+    }
+}
+
+/// Replaces a track's lyrics and credits. Blank lyrics are cleared.
+pub fn update_track_details(
+    track_id: String,
+    lyrics: Option<String>,
+    credits: Vec<TrackCredit>,
+) -> Result<TrackDetails, String> {
+    let actor = require_scope(Scope::TracksWrite)?;
+    let caller = actor.account;
+
+    let track = get_track_by_id(&track_id)
+        .ok_or_else(|| "Track not found".to_string())?;
+
+    let project = get_track_project(&track)?;
+
+    if !is_project_member(&project, caller) {
+        return Err("Only project owner or collaborators can update tracks".to_string());
+    }
+
+    ensure_not_archived(&project)?;
+    ensure_editable(&track)?;
+
+    let lyrics = lyrics
+        .map(|lyrics| lyrics.replace("\r\n", "\n").trim().to_string())
+        .filter(|lyrics| !lyrics.is_empty());
+    let credits: Vec<TrackCredit> = credits
+        .into_iter()
+        .map(|credit| TrackCredit { name: credit.name.trim().to_string(), ..credit })
+        .collect();
+
+    validation::validate_track_details(lyrics.as_deref(), &credits)?;
+
+    let details = TrackDetails { track_id: track_id.clone(), lyrics, credits, updated_at: time() };
+
+    if details.lyrics.is_none() && details.credits.is_empty() {
+        storage::remove_track_details(&track_id);
+    } else {
+        save_track_details(details.clone());
+    }
+
+    activity::record(&project.id, caller, ActivityKind::TrackUpdated { track_id });
+    Ok(details)
+}
+
+pub fn get_track_details(track_id: String) -> Result<TrackDetails, String> {
+    get_visible_track(&track_id)?;
+
+    Ok(storage::get_track_details(&track_id).unwrap_or(TrackDetails {
+        track_id,
+        lyrics: None,
+        credits: vec![],
+        updated_at: 0,
+    }))
+}
+
+/// Uploads a new take or mix. With `activate` set it also becomes the
+/// track's current content.
+pub fn add_track_version(
+    track_id: String,
+    ipfs_hash: String,
+    duration: u64,
+    notes: Option<String>,
+    activate_version: bool,
+) -> Result<TrackVersion, String> {
+    let actor = require_scope(Scope::TracksWrite)?;
+    let caller = actor.account;
+
+    let mut track = get_track_by_id(&track_id)
+        .ok_or_else(|| "Track not found".to_string())?;
+
+    let project = get_track_project(&track)?;
+
+    if !is_project_member(&project, caller) {
+        return Err("Only project owner or collaborators can add versions".to_string());
+    }
+
+    ensure_not_archived(&project)?;
+    ensure_editable(&track)?;
+
+    validation::validate_cid(&ipfs_hash)?;
+
+    let version = add_version(&track, ipfs_hash, duration, notes, caller)?;
+    activity::record(
+        &project.id,
+        caller,
+        ActivityKind::TrackVersionAdded { track_id, version: version.number },
+    );
+
+    if activate_version {
+        activate(&mut track, &version);
+        save_track(track);
+    }
+
+    Ok(version)
+}
+
+/// Makes an earlier or later version the track's current content.
+pub fn set_active_track_version(track_id: String, version: u64) -> Result<Track, String> {
+    let actor = require_scope(Scope::TracksWrite)?;
+    let caller = actor.account;
+
+    let mut track = get_track_by_id(&track_id)
+        .ok_or_else(|| "Track not found".to_string())?;
+
+    let project = get_track_project(&track)?;
+
+    if !is_project_member(&project, caller) {
+        return Err("Only project owner or collaborators can change the active version".to_string());
+    }
+
+    ensure_not_archived(&project)?;
+    ensure_editable(&track)?;
+
+    let version = get_track_version(&track_id, version)
+        .ok_or_else(|| "Version not found".to_string())?;
+
+    activate(&mut track, &version);
+    save_track(track.clone());
+    activity::record(
+        &project.id,
+        caller,
+        ActivityKind::TrackVersionActivated { track_id, version: version.number },
+    );
+
+    Ok(track)
+}
+
+/// A track's versions, oldest first.
+pub fn get_track_versions(track_id: String) -> Result<Vec<TrackVersion>, String> {
+    get_visible_track(&track_id)?;
+    Ok(stored_versions(&track_id))
+}
+
+/// Lists what differs between two versions of a track.
+pub fn compare_track_versions(track_id: String, from: u64, to: u64) -> Result<TrackVersionComparison, String> {
+    get_visible_track(&track_id)?;
+
+    let from = get_track_version(&track_id, from)
+        .ok_or_else(|| format!("Version {} not found", from))?;
+    let to = get_track_version(&track_id, to)
+        .ok_or_else(|| format!("Version {} not found", to))?;
+
+    let mut changes = Vec::new();
+    let mut field = |name: &str, before: Option<String>, after: Option<String>| {
+        if before != after {
+            changes.push(RevisionChange::Field { name: name.to_string(), before, after });
+        }
+    };
+
+    field("ipfs_hash", Some(from.ipfs_hash.clone()), Some(to.ipfs_hash.clone()));
+    field("duration", Some(from.duration.to_string()), Some(to.duration.to_string()));
+    field("uploaded_by", Some(from.uploaded_by.to_text()), Some(to.uploaded_by.to_text()));
+    field("notes", from.notes.clone(), to.notes.clone());
+
+    Ok(TrackVersionComparison {
+        duration_delta: to.duration as i64 - from.duration as i64,
+        changes,
+        from,
+        to,
+    })
+}
+
+/// Stores `track`'s content as version 1 and makes it active. Call this
+/// for newly created tracks before saving them.
+pub fn record_initial_version(track: &mut Track, uploaded_by: Principal) {
+    let version = push_track_version(TrackVersion {
+        track_id: track.id.clone(),
+        number: 0,
+        ipfs_hash: track.ipfs_hash.clone(),
+        duration: track.duration,
+        uploaded_by,
+        notes: None,
+        created_at: track.created_at,
+    });
+
+    track.active_version = Some(version.number);
+}
+
+/// Stores the next version of `track`. Callers validate `ipfs_hash` when it
+/// is new; a hash carried over from the track is stored as is.
+fn add_version(
+    track: &Track,
+    ipfs_hash: String,
+    duration: u64,
+    notes: Option<String>,
+    uploaded_by: Principal,
+) -> Result<TrackVersion, String> {
+    if let Some(notes) = &notes {
+        validation::validate_version_notes(notes)?;
+    }
+
+    if count_track_versions(&track.id) >= MAX_VERSIONS_PER_TRACK {
+        return Err(format!("A track cannot have more than {} versions", MAX_VERSIONS_PER_TRACK));
+    }
+
+    Ok(push_track_version(TrackVersion {
+        track_id: track.id.clone(),
+        number: 0,
+        ipfs_hash,
+        duration,
+        uploaded_by,
+        notes,
+        created_at: time(),
+    }))
+}
+
+fn activate(track: &mut Track, version: &TrackVersion) {
+    track.ipfs_hash = version.ipfs_hash.clone();
+    track.duration = version.duration;
+    track.active_version = Some(version.number);
+}
+
+pub fn add_stem(
+    track_id: String,
+    role: StemRole,
+    ipfs_hash: String,
+    sample_rate: u32,
+    channels: u8,
+) -> Result<Stem, String> {
+    let actor = require_scope(Scope::TracksWrite)?;
+    let caller = actor.account;
+
+    let track = get_track_by_id(&track_id)
+        .ok_or_else(|| "Track not found".to_string())?;
+
+    let project = get_track_project(&track)?;
+
+    if !is_project_member(&project, caller) {
+        return Err("Only project owner or collaborators can add stems".to_string());
+    }
+
+    ensure_not_archived(&project)?;
+    ensure_editable(&track)?;
+    validation::validate_cid(&ipfs_hash)?;
+    validation::validate_audio_format(sample_rate, channels)?;
+
+    if storage::get_track_stems(&track_id).len() >= MAX_STEMS_PER_TRACK {
+        return Err(format!("A track cannot have more than {} stems", MAX_STEMS_PER_TRACK));
+    }
+
+    let now = time();
+    let stem = Stem {
+        id: Uuid::new_v4().to_string(),
+        track_id: track_id.clone(),
+        role,
+        ipfs_hash,
+        contributor: caller,
+        sample_rate,
+        channels,
+        created_at: now,
+        updated_at: now,
+    };
+
+    save_stem(stem.clone());
+    activity::record(&project.id, caller, ActivityKind::StemAdded { track_id, stem_id: stem.id.clone() });
+
+    Ok(stem)
+}
+
+/// Swaps in new audio for a stem, e.g. a re-recorded vocal. The caller
+/// becomes the stem's contributor.
+pub fn replace_stem(
+    track_id: String,
+    stem_id: String,
+    ipfs_hash: String,
+    sample_rate: u32,
+    channels: u8,
+) -> Result<Stem, String> {
+    let actor = require_scope(Scope::TracksWrite)?;
+    let caller = actor.account;
+
+    let track = get_track_by_id(&track_id)
+        .ok_or_else(|| "Track not found".to_string())?;
+
+    let project = get_track_project(&track)?;
+
+    if !is_project_member(&project, caller) {
+        return Err("Only project owner or collaborators can replace stems".to_string());
+    }
+
+    ensure_not_archived(&project)?;
+    ensure_editable(&track)?;
+
+    let mut stem = get_stem(&track_id, &stem_id)
+        .ok_or_else(|| "Stem not found".to_string())?;
+
+    validation::validate_cid(&ipfs_hash)?;
+    validation::validate_audio_format(sample_rate, channels)?;
+
+    stem.ipfs_hash = ipfs_hash;
+    stem.sample_rate = sample_rate;
+    stem.channels = channels;
+    stem.contributor = caller;
+    stem.updated_at = time();
+
+    save_stem(stem.clone());
+    activity::record(&project.id, caller, ActivityKind::StemReplaced { track_id, stem_id });
+
+    Ok(stem)
+}
+
+pub fn remove_stem(track_id: String, stem_id: String) -> Result<(), String> {
+    let actor = require_scope(Scope::TracksWrite)?;
+    let caller = actor.account;
+
+    let track = get_track_by_id(&track_id)
+        .ok_or_else(|| "Track not found".to_string())?;
+
+    let project = get_track_project(&track)?;
+
+    if !is_project_member(&project, caller) {
+        return Err("Only project owner or collaborators can remove stems".to_string());
+    }
+
+    ensure_not_archived(&project)?;
+    ensure_editable(&track)?;
+
+    if get_stem(&track_id, &stem_id).is_none() {
+        return Err("Stem not found".to_string());
+    }
+
+    storage::remove_stem(&track_id, &stem_id);
+    activity::record(&project.id, caller, ActivityKind::StemRemoved { track_id, stem_id });
+
+    Ok(())
+}
+
+pub fn get_track_stems(track_id: String) -> Result<Vec<Stem>, String> {
+    get_visible_track(&track_id)?;
+    Ok(storage::get_track_stems(&track_id))
+}
+
+/// Removes a track and its entry in `Project.tracks`. Tracks of projects
+/// with minted NFTs are kept, since the tokens point at them.
+pub fn delete_track(track_id: String) -> Result<(), String> {
+    let actor = require_scope(Scope::TracksWrite)?;
+    let caller = actor.account;
+
+    let track = get_track_by_id(&track_id)
+        .ok_or_else(|| "Track not found".to_string())?;
+
+    let mut project = get_track_project(&track)?;
+
+    if !is_project_member(&project, caller) {
+        return Err("Only project owner or collaborators can delete tracks".to_string());
+    }
+
+    ensure_not_archived(&project)?;
+    ensure_editable(&track)?;
+
+    let nfts = get_nfts_by_project(&project.id);
+    if nfts.iter().any(|nft| nft.is_minted) {
+        return Err("Tracks of projects with minted NFTs cannot be deleted".to_string());
+    }
+
+    project.tracks.retain(|id| id != &track_id);
+    project.updated_at = time();
+
+    // Every NFT of the project is unminted here, so none may keep pointing at the track
+    for mut nft in nfts {
+        if remove_nft_pin(&nft.id, &track_id) {
+            nft.updated_at = time();
+            save_nft(nft);
+        }
+    }
+
+    remove_track(&track_id);
+    save_project_with_history(project.clone(), caller);
+    activity::record(&project.id, caller, ActivityKind::TrackRemoved { track_id });
+
+    Ok(())
+}
+
+/// Sets the tracklist order. `track_ids` must list every track of the
+/// project exactly once.
+pub fn reorder_tracks(project_id: String, track_ids: Vec<String>) -> Result<Project, String> {
+    let actor = require_scope(Scope::TracksWrite)?;
+    let caller = actor.account;
+
+    let mut project = get_project_by_id(&project_id)
+        .ok_or_else(|| "Project not found".to_string())?;
+
+    if !is_project_member(&project, caller) {
+        return Err("Only project owner or collaborators can reorder tracks".to_string());
+    }
+
+    ensure_not_archived(&project)?;
+
+    let mut requested = track_ids.clone();
+    let mut existing = project.tracks.clone();
+    requested.sort();
+    existing.sort();
+
+    if requested != existing {
+        return Err("New order must contain each of the project's tracks exactly once".to_string());
+    }
+
+    if project.tracks == track_ids {
+        return Ok(project);
+    }
+
+    project.tracks = track_ids;
+    project.updated_at = time();
+
+    save_project_with_history(project.clone(), caller);
+    activity::record(&project_id, caller, ActivityKind::TracksReordered);
+
+    Ok(project)
+}
+
+/// A track's status transitions, oldest first.
+pub fn get_track_status_history(track_id: String) -> Result<Vec<TrackStatusChange>, String> {
+    get_visible_track(&track_id)?;
+    Ok(storage::get_track_status_history(&track_id))
+}
+
+fn record_status_change(track: &Track, to: TrackStatus, changed_by: Principal) {
+    push_track_status_change(TrackStatusChange {
+        track_id: track.id.clone(),
+        sequence: 0,
+        from: track.status.clone(),
+        to,
+        changed_by,
+        changed_at: time(),
+    });
+}
+
+fn ensure_editable(track: &Track) -> Result<(), String> {
+    if track.status == TrackStatus::Completed {
+        return Err("Completed tracks are locked; move the track back to Mixing first".to_string());
+    }
+
+    Ok(())
+}
+
+fn get_visible_track(track_id: &str) -> Result<Track, String> {
+    get_track_by_id(track_id)
+        .filter(|track| get_visible_project(&track.project_id, caller_principal()).is_some())
+        .ok_or_else(|| "Track not found".to_string())
+}
+
+fn get_track_project(track: &Track) -> Result<Project, String> {
+    get_project_by_id(&track.project_id)
+        .ok_or_else(|| "Project not found".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use ic_stable_structures::{BoundedStorable, Storable};
+    use super::*;
+
+    #[test]
+    fn a_version_at_every_limit_fits_its_bound() {
+        // Quotes are stored escaped, so they take the most room per character
+        let notes = "\"".repeat(400);
+        validation::validate_version_notes(&notes).unwrap();
+        assert!(validation::validate_version_notes(&format!("{}\"", notes)).is_err());
+
+        let ipfs_hash = format!("b{}", "a".repeat(99));
+        validation::validate_cid(&ipfs_hash).unwrap();
+
+        let version = TrackVersion {
+            track_id: Uuid::nil().to_string(),
+            number: u64::MAX,
+            ipfs_hash,
+            duration: u64::MAX,
+            uploaded_by: Principal::from_slice(&[255; 29]),
+            notes: Some(notes),
+            created_at: u64::MAX,
+        };
+
+        let size = version.to_bytes().len();
+        assert!(size <= TrackVersion::MAX_SIZE as usize, "{} bytes", size);
+    }
+
+    #[test]
+    fn version_notes_are_limited_by_characters_and_bytes() {
+        assert!(validation::validate_version_notes(&"a".repeat(500)).is_ok());
+        assert!(validation::validate_version_notes(&"a".repeat(501)).is_err());
+        assert!(validation::validate_version_notes(&"🎸".repeat(201)).is_err());
+        assert!(validation::validate_version_notes("take two\nlouder").is_ok());
+        assert!(validation::validate_version_notes("bell\u{7}").is_err());
+    }
+}
@@ -6,10 +6,216 @@ use std::borrow::Cow;
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct User {
     pub principal: Principal,
+    #[serde(default)]
+    pub handle: Option<String>,
+    #[serde(default)]
+    pub handle_updated_at: Option<u64>,
     pub name: Option<String>,
     pub email: String,
+    #[serde(default)]
+    pub email_status: EmailStatus,
+    #[serde(default)]
+    pub email_verified_at: Option<u64>,
     pub avatar_url: Option<String>,
+    #[serde(default)]
+    pub bio: Option<String>,
+    #[serde(default)]
+    pub links: Vec<String>,
+    #[serde(default)]
+    pub location: Option<String>,
+    #[serde(default)]
+    pub privacy: PrivacySettings,
+    #[serde(default)]
+    pub follower_count: u64,
+    #[serde(default)]
+    pub following_count: u64,
     pub created_at: u64,
+    #[serde(default)]
+    pub updated_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum EmailStatus {
+    #[default]
+    Unverified,
+    /// A verification code has been sent and not yet redeemed
+    Pending,
+    Verified,
+}
+
+/// Outstanding verification code for a user's current email address.
+/// Only a hash of the code is stored.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct EmailVerification {
+    pub principal: Principal,
+    pub email: String,
+    pub code_hash: String,
+    pub attempts: u8,
+    pub created_at: u64,
+    pub expires_at: u64,
+}
+
+/// A message waiting in the outbox for an off-chain mailer to deliver.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct OutboundEmail {
+    pub id: u64,
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+    pub created_at: u64,
+}
+
+/// Controls which optional profile fields appear in `PublicProfile`.
+/// Name, handle and avatar are always public.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PrivacySettings {
+    pub show_email: bool,
+    pub show_bio: bool,
+    pub show_links: bool,
+    pub show_location: bool,
+}
+
+impl Default for PrivacySettings {
+    fn default() -> Self {
+        PrivacySettings {
+            show_email: false,
+            show_bio: true,
+            show_links: true,
+            show_location: false,
+        }
+    }
+}
+
+/// Privileged roles, ordered from least to most privileged. Principals
+/// without a stored role are regular users.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    User,
+    Moderator,
+    Admin,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RoleAssignment {
+    pub principal: Principal,
+    pub role: Role,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SuspensionKind {
+    Suspended,
+    Banned,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Suspension {
+    pub principal: Principal,
+    pub kind: SuspensionKind,
+    pub reason: String,
+    pub issued_by: Principal,
+    pub issued_at: u64,
+    /// `None` for permanent bans
+    pub expires_at: Option<u64>,
+}
+
+/// An additional principal (e.g. another Internet Identity origin or
+/// device) that acts on behalf of `account`.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct LinkedPrincipal {
+    pub principal: Principal,
+    pub account: Principal,
+    pub linked_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct LinkChallenge {
+    pub code: String,
+    pub account: Principal,
+    pub created_at: u64,
+    pub expires_at: u64,
+}
+
+/// Actions a delegate principal may perform on behalf of an account.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    TracksWrite,
+    NftsCreate,
+}
+
+/// Grants `delegate` (e.g. a release bot) limited access to `account`.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Delegation {
+    pub delegate: Principal,
+    pub account: Principal,
+    pub scopes: Vec<Scope>,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub revoked_at: Option<u64>,
+    pub last_used_at: Option<u64>,
+}
+
+/// What happens to the projects a user owns when they delete their account.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum ProjectDisposition {
+    Archive,
+    Transfer(Principal),
+}
+
+/// Every record the canister holds about a user.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct UserDataExport {
+    pub user: User,
+    pub role: Role,
+    pub linked_principals: Vec<LinkedPrincipal>,
+    pub delegations: Vec<Delegation>,
+    pub projects: Vec<Project>,
+    /// Tracks in the user's projects and tracks they uploaded audio to
+    pub tracks: Vec<Track>,
+    pub track_versions: Vec<TrackVersion>,
+    pub stems: Vec<Stem>,
+    pub nfts: Vec<NFT>,
+    pub collaborations: Vec<Collaboration>,
+    pub invitations: Vec<Invitation>,
+    pub ownership_transfers: Vec<OwnershipTransfer>,
+    pub following: Vec<Principal>,
+    pub followers: Vec<Principal>,
+    /// Undelivered mail addressed to the user
+    pub emails: Vec<OutboundEmail>,
+    pub exported_at: u64,
+}
+
+/// Arguments accepted by both `init` and `post_upgrade`.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct InitArgs {
+    pub admins: Vec<Principal>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct PublicProfile {
+    pub principal: Principal,
+    pub handle: Option<String>,
+    pub name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub email: Option<String>,
+    pub bio: Option<String>,
+    pub links: Vec<String>,
+    pub location: Option<String>,
+    pub follower_count: u64,
+    pub following_count: u64,
+    pub created_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct FollowEntry {
+    pub principal: Principal,
+    pub followed_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct FollowPage {
+    pub entries: Vec<FollowEntry>,
+    /// Pass back as `start_after` to fetch the next page
+    pub next_cursor: Option<Principal>,
 }
 
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
@@ -21,10 +227,155 @@ pub struct Project {
     pub collaborators: Vec<String>,
     pub tracks: Vec<String>,
     pub nfts: Vec<String>,
+    #[serde(default)]
+    pub visibility: ProjectVisibility,
+    #[serde(default)]
+    pub genres: Vec<String>,
+    #[serde(default)]
+    pub moods: Vec<String>,
+    /// Free-form tags; like genres and moods they are stored lowercased
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Whether other users may fork this project
+    #[serde(default)]
+    pub allow_remixes: bool,
+    /// Share of NFT royalties forks owe this project, fixed when they fork
+    #[serde(default)]
+    pub upstream_royalty_percentage: u8,
+    /// The project this one was forked from
+    #[serde(default)]
+    pub parent_project_id: Option<String>,
+    /// Royalty shares owed to every ancestor that asked for one
+    #[serde(default)]
+    pub upstream_royalties: Vec<RoyaltyShare>,
+    /// Set when the project is frozen, e.g. after its owner deleted their account
+    #[serde(default)]
+    pub archived_at: Option<u64>,
     pub created_at: u64,
     pub updated_at: u64,
 }
 
+/// Who can see a project. Only the owner can see `Private` projects;
+/// `Unlisted` projects are readable by id but left out of listings.
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ProjectVisibility {
+    Private,
+    CollaboratorsOnly,
+    Unlisted,
+    /// Default for records stored before visibility existed
+    #[default]
+    Public,
+}
+
+/// A pending handover of `Project.owner`. The recipient must accept before
+/// `expires_at`; if `requires_collaborator_approval` is set, every
+/// collaborator other than the recipient must approve first.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct OwnershipTransfer {
+    pub project_id: String,
+    pub from: Principal,
+    pub to: Principal,
+    pub requires_collaborator_approval: bool,
+    pub approvals: Vec<Principal>,
+    pub proposed_at: u64,
+    pub expires_at: u64,
+}
+
+/// An immutable record of one change to a project. Revisions are numbered
+/// from 1 per project; a labeled revision is a named snapshot.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ProjectRevision {
+    pub project_id: String,
+    pub number: u64,
+    pub author: Principal,
+    pub created_at: u64,
+    pub label: Option<String>,
+    /// Derived from the previous revision's snapshot when read, so each
+    /// revision stores the project only once. Revisions recorded before
+    /// that still carry their own.
+    #[serde(default, skip_serializing)]
+    pub changes: Vec<RevisionChange>,
+    /// The project as it was after this revision
+    pub snapshot: ProjectSnapshot,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum RevisionChange {
+    Field {
+        name: String,
+        before: Option<String>,
+        after: Option<String>,
+    },
+    TrackAdded(String),
+    TrackRemoved(String),
+    TracksReordered,
+    CollaboratorAdded(String),
+    CollaboratorRemoved(String),
+}
+
+/// The versioned parts of a project.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ProjectSnapshot {
+    pub name: String,
+    pub description: Option<String>,
+    pub visibility: ProjectVisibility,
+    pub genres: Vec<String>,
+    pub moods: Vec<String>,
+    pub tags: Vec<String>,
+    pub tracks: Vec<String>,
+    pub collaborators: Vec<String>,
+    #[serde(default)]
+    pub owner: Option<Principal>,
+    #[serde(default)]
+    pub archived: bool,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct RevisionPage {
+    pub items: Vec<ProjectRevision>,
+    pub next_cursor: Option<String>,
+}
+
+/// One entry in a project's append-only activity log. Ids increase
+/// across all projects, so feeds can merge logs by id.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ActivityEvent {
+    pub id: u64,
+    pub project_id: String,
+    pub actor: Principal,
+    pub kind: ActivityKind,
+    pub created_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ActivityKind {
+    ProjectCreated,
+    ProjectUpdated,
+    ProjectArchived,
+    ProjectRestored,
+    TrackAdded { track_id: String },
+    TrackStatusChanged { track_id: String, status: TrackStatus },
+    TrackUpdated { track_id: String },
+    TrackRemoved { track_id: String },
+    TracksReordered,
+    TrackVersionAdded { track_id: String, version: u64 },
+    TrackVersionActivated { track_id: String, version: u64 },
+    StemAdded { track_id: String, stem_id: String },
+    StemReplaced { track_id: String, stem_id: String },
+    StemRemoved { track_id: String, stem_id: String },
+    CollaboratorJoined { principal: Principal },
+    CollaboratorRemoved { principal: Principal },
+    OwnershipTransferred { from: Principal, to: Principal },
+    NftCreated { nft_id: String },
+    NftMinted { nft_id: String },
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ActivityPage {
+    pub items: Vec<ActivityEvent>,
+    pub next_cursor: Option<String>,
+}
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct Track {
     pub id: String,
@@ -33,10 +384,124 @@ pub struct Track {
     pub ipfs_hash: String,
     pub duration: u64,
     pub status: TrackStatus,
+    /// Delegate principal that added the track on the owner's behalf, if any
+    #[serde(default)]
+    pub delegate: Option<Principal>,
+    /// Number of the `TrackVersion` that `ipfs_hash` and `duration` mirror
+    #[serde(default)]
+    pub active_version: Option<u64>,
+    #[serde(default)]
+    pub metadata: TrackMetadata,
     pub created_at: u64,
 }
 
+/// Descriptive data used for distribution and NFT metadata.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TrackMetadata {
+    pub bpm: Option<u16>,
+    /// e.g. "C", "F#m", "Bb minor"
+    pub key: Option<String>,
+    /// e.g. "4/4", "6/8"
+    pub time_signature: Option<String>,
+    pub genre: Option<String>,
+    /// Stored without hyphens, e.g. "USRC17607839"
+    pub isrc: Option<String>,
+    pub explicit: bool,
+    /// ISO 639-1 code, e.g. "en"
+    pub language: Option<String>,
+}
+
+/// Lyrics and credits, stored apart from `Track` so tracks stay small.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TrackDetails {
+    pub track_id: String,
+    pub lyrics: Option<String>,
+    pub credits: Vec<TrackCredit>,
+    pub updated_at: u64,
+}
+
+/// A writer or performer credit. `principal` links the credit to an
+/// NFTune account when the person has one.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TrackCredit {
+    pub name: String,
+    pub principal: Option<Principal>,
+    pub role: CreditRole,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CreditRole {
+    Composer,
+    Lyricist,
+    Songwriter,
+    Producer,
+    Performer,
+    FeaturedArtist,
+    Engineer,
+}
+
+/// One take or mix of a track. Versions are numbered from 1 and never change.
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TrackVersion {
+    pub track_id: String,
+    pub number: u64,
+    pub ipfs_hash: String,
+    pub duration: u64,
+    pub uploaded_by: Principal,
+    pub notes: Option<String>,
+    pub created_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TrackVersionComparison {
+    pub from: TrackVersion,
+    pub to: TrackVersion,
+    /// Fields that differ between the two versions
+    pub changes: Vec<RevisionChange>,
+    /// `to.duration - from.duration`, in seconds
+    pub duration_delta: i64,
+}
+
+/// The exact version of a track an NFT's content refers to.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PinnedTrackVersion {
+    pub track_id: String,
+    pub version: u64,
+}
+
+/// An isolated part of a track, such as the drums or lead vocal, shared
+/// so collaborators can remix or re-balance it.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Stem {
+    pub id: String,
+    pub track_id: String,
+    pub role: StemRole,
+    pub ipfs_hash: String,
+    /// Who uploaded the current audio
+    pub contributor: Principal,
+    /// In Hz, e.g. 48000
+    pub sample_rate: u32,
+    pub channels: u8,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StemRole {
+    Drums,
+    Percussion,
+    Bass,
+    Keys,
+    Guitar,
+    Vocals,
+    BackingVocals,
+    Synth,
+    Strings,
+    Fx,
+    Other,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub enum TrackStatus {
     Draft,
     Recording,
@@ -44,6 +509,43 @@ pub enum TrackStatus {
     Completed,
 }
 
+/// One step in a track's status history.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TrackStatusChange {
+    pub track_id: String,
+    pub sequence: u64,
+    pub from: TrackStatus,
+    pub to: TrackStatus,
+    pub changed_by: Principal,
+    pub changed_at: u64,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RoyaltyShare {
+    pub project_id: String,
+    pub recipient: Principal,
+    pub percentage: u8,
+}
+
+/// A project in another project's fork tree that the caller can view.
+/// `parent_project_id` is left out when the parent is hidden from them.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct LineageEntry {
+    pub project_id: String,
+    pub parent_project_id: Option<String>,
+    pub name: String,
+    /// Steps away from the queried project
+    pub depth: u32,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ProjectLineage {
+    /// Nearest first
+    pub ancestors: Vec<LineageEntry>,
+    /// Breadth-first
+    pub descendants: Vec<LineageEntry>,
+}
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct NFT {
     pub id: String,
@@ -59,10 +561,40 @@ pub struct NFT {
     pub is_minted: bool,
     pub is_listed: bool,
     pub opensea_url: Option<String>,
+    /// Delegate principal that created the NFT on the creator's behalf, if any
+    #[serde(default)]
+    pub delegate: Option<Principal>,
+    /// Paid on top of `royalty_percentage` to the projects this one remixes
+    #[serde(default)]
+    pub upstream_royalties: Vec<RoyaltyShare>,
     pub created_at: u64,
     pub updated_at: u64,
 }
 
+/// An offer to join a project. Only accepting it creates a `Collaboration`.
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct Invitation {
+    pub id: String,
+    pub project_id: String,
+    pub invitee: Principal,
+    pub invited_by: Principal,
+    pub role: String,
+    pub contribution_percentage: u8,
+    pub status: InvitationStatus,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub responded_at: Option<u64>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvitationStatus {
+    Pending,
+    Accepted,
+    Declined,
+    Revoked,
+    Expired,
+}
+
 #[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
 pub struct Collaboration {
     pub id: String,
@@ -73,7 +605,134 @@ pub struct Collaboration {
     pub joined_at: u64,
 }
 
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct PageRequest {
+    /// `next_cursor` from the previous page; omit for the first page
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortField {
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+/// Defaults to newest-created first, excluding archived projects.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct ProjectQuery {
+    pub page: PageRequest,
+    pub sort: Option<SortField>,
+    pub direction: Option<SortDirection>,
+    pub owner: Option<Principal>,
+    pub archived: Option<bool>,
+}
+
+/// Defaults to newest-created first.
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct NftQuery {
+    pub page: PageRequest,
+    pub sort: Option<SortField>,
+    pub direction: Option<SortDirection>,
+    pub creator: Option<Principal>,
+    pub project_id: Option<String>,
+    pub minted: Option<bool>,
+    pub listed: Option<bool>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, Default)]
+pub struct TrackQuery {
+    pub page: PageRequest,
+    pub status: Option<TrackStatus>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct ProjectPage {
+    pub items: Vec<Project>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct NftPage {
+    pub items: Vec<NFT>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct TrackPage {
+    pub items: Vec<Track>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct CollaborationPage {
+    pub items: Vec<Collaboration>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchKind {
+    Project,
+    Track,
+    Nft,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug)]
+pub struct SearchQuery {
+    /// Every word must match a whole indexed word or the start of one
+    pub text: String,
+    /// Restricts results to these kinds; omit to search everything
+    pub kinds: Option<Vec<SearchKind>>,
+    pub page: PageRequest,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub enum SearchHit {
+    Project(Project),
+    Track(Track),
+    Nft(NFT),
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SearchResult {
+    pub score: u32,
+    pub hit: SearchHit,
+}
+
+#[derive(CandidType, Serialize, Deserialize, Clone, Debug)]
+pub struct SearchPage {
+    pub items: Vec<SearchResult>,
+    pub next_cursor: Option<String>,
+}
+
 // Implement Storable for stable storage
+
+/// A record in a map created with an older, smaller `MAX_SIZE`. A map's
+/// bound can't grow in place, so a type that outgrows it moves to a new
+/// memory and a migration drains the old map through this wrapper.
+pub struct Legacy<T, const MAX_SIZE: u32>(pub T);
+
+impl<T: Storable, const MAX_SIZE: u32> Storable for Legacy<T, MAX_SIZE> {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        self.0.to_bytes()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Legacy(T::from_bytes(bytes))
+    }
+}
+
+impl<T: Storable, const MAX_SIZE: u32> BoundedStorable for Legacy<T, MAX_SIZE> {
+    const MAX_SIZE: u32 = MAX_SIZE;
+    const IS_FIXED_SIZE: bool = false;
+}
+
 impl Storable for User {
     fn to_bytes(&self) -> Cow<[u8]> {
         Cow::Owned(serde_json::to_vec(self).unwrap())
@@ -85,7 +744,7 @@ impl Storable for User {
 }
 
 impl BoundedStorable for User {
-    const MAX_SIZE: u32 = 1024;
+    const MAX_SIZE: u32 = 8192;
     const IS_FIXED_SIZE: bool = false;
 }
 
@@ -99,8 +758,10 @@ impl Storable for Project {
     }
 }
 
+// With the caps on names, descriptions, labels, tracks, collaborators and
+// upstream shares, a project serializes to at most about 15 KiB.
 impl BoundedStorable for Project {
-    const MAX_SIZE: u32 = 2048;
+    const MAX_SIZE: u32 = 16384;
     const IS_FIXED_SIZE: bool = false;
 }
 
@@ -114,8 +775,10 @@ impl Storable for NFT {
     }
 }
 
+// Upstream shares and a delegate take up to about 1.5 KiB; the capped
+// title, description, metadata URI and token fields take the rest.
 impl BoundedStorable for NFT {
-    const MAX_SIZE: u32 = 2048;
+    const MAX_SIZE: u32 = 8192;
     const IS_FIXED_SIZE: bool = false;
 }
 
@@ -144,7 +807,234 @@ impl Storable for Track {
     }
 }
 
+// Names are capped at 64 characters and CIDs at 100, which keeps a track
+// and its metadata under 1 KiB.
 impl BoundedStorable for Track {
     const MAX_SIZE: u32 = 1024;
     const IS_FIXED_SIZE: bool = false;
-}
\ No newline at end of file
+}
+
+impl Storable for Role {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(serde_json::to_vec(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).unwrap()
+    }
+}
+
+impl BoundedStorable for Role {
+    const MAX_SIZE: u32 = 32;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for Suspension {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(serde_json::to_vec(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).unwrap()
+    }
+}
+
+impl BoundedStorable for Suspension {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for LinkedPrincipal {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(serde_json::to_vec(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).unwrap()
+    }
+}
+
+impl BoundedStorable for LinkedPrincipal {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for LinkChallenge {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(serde_json::to_vec(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).unwrap()
+    }
+}
+
+impl BoundedStorable for LinkChallenge {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for Delegation {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(serde_json::to_vec(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).unwrap()
+    }
+}
+
+impl BoundedStorable for Delegation {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for EmailVerification {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(serde_json::to_vec(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).unwrap()
+    }
+}
+
+impl BoundedStorable for EmailVerification {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for OutboundEmail {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(serde_json::to_vec(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).unwrap()
+    }
+}
+
+impl BoundedStorable for OutboundEmail {
+    const MAX_SIZE: u32 = 4096;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for OwnershipTransfer {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(serde_json::to_vec(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).unwrap()
+    }
+}
+
+impl BoundedStorable for OwnershipTransfer {
+    const MAX_SIZE: u32 = 4096;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for ProjectRevision {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(serde_json::to_vec(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).unwrap()
+    }
+}
+
+impl BoundedStorable for ProjectRevision {
+    const MAX_SIZE: u32 = 16384;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for ActivityEvent {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(serde_json::to_vec(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).unwrap()
+    }
+}
+
+impl BoundedStorable for ActivityEvent {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for Invitation {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(serde_json::to_vec(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).unwrap()
+    }
+}
+
+impl BoundedStorable for Invitation {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for TrackStatusChange {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(serde_json::to_vec(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).unwrap()
+    }
+}
+
+impl BoundedStorable for TrackStatusChange {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for TrackVersion {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(serde_json::to_vec(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).unwrap()
+    }
+}
+
+impl BoundedStorable for TrackVersion {
+    const MAX_SIZE: u32 = 2048;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for TrackDetails {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(serde_json::to_vec(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).unwrap()
+    }
+}
+
+impl BoundedStorable for TrackDetails {
+    const MAX_SIZE: u32 = 24576;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+impl Storable for Stem {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(serde_json::to_vec(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        serde_json::from_slice(&bytes).unwrap()
+    }
+}
+
+impl BoundedStorable for Stem {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
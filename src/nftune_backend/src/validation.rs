@@ -0,0 +1,642 @@
+use crate::types::{TrackCredit, TrackMetadata};
+
+const MAX_NAME_LENGTH: usize = 64;
+const MAX_EMAIL_LENGTH: usize = 254;
+const MAX_URL_LENGTH: usize = 512;
+const MAX_LINK_LENGTH: usize = 256;
+const MAX_CID_LENGTH: usize = 100;
+const MAX_BIO_LENGTH: usize = 1000;
+const MAX_BIO_BYTES: usize = 2000;
+const MAX_DESCRIPTION_LENGTH: usize = 1000;
+const MAX_LOCATION_LENGTH: usize = 100;
+const MAX_LINKS: usize = 10;
+const MIN_HANDLE_LENGTH: usize = 3;
+const MAX_HANDLE_LENGTH: usize = 30;
+const MAX_TAGS: usize = 10;
+const MAX_TAG_LENGTH: usize = 32;
+const MAX_SNAPSHOT_LABEL_LENGTH: usize = 64;
+const MAX_ROLE_LENGTH: usize = 32;
+const MAX_TITLE_LENGTH: usize = 100;
+/// A uint256 in decimal
+const MAX_TOKEN_ID_LENGTH: usize = 78;
+const MAX_SUSPENSION_REASON_LENGTH: usize = 140;
+const MAX_VERSION_NOTES_LENGTH: usize = 500;
+const MAX_VERSION_NOTES_BYTES: usize = 800;
+const MIN_BPM: u16 = 20;
+const MAX_BPM: u16 = 400;
+const MAX_LYRICS_LENGTH: usize = 4000;
+const MAX_CREDITS: usize = 20;
+const SAMPLE_RATES: &[u32] = &[44_100, 48_000, 88_200, 96_000, 176_400, 192_000];
+const MAX_CHANNELS: u8 = 8;
+
+/// Handles that could be mistaken for platform accounts or collide with frontend routes.
+const RESERVED_HANDLES: &[&str] = &[
+    "admin", "administrator", "api", "app", "help", "login", "logout", "me",
+    "moderator", "nftune", "nfts", "projects", "root", "settings", "support",
+    "system", "tracks", "user", "users",
+];
+
+/// Bytes `text` takes up once stored. Records are serialized as JSON, which
+/// escapes quotes, backslashes and line breaks with a second byte.
+pub fn stored_len(text: &str) -> usize {
+    text.len() + text.chars().filter(|c| matches!(c, '"' | '\\' | '\n')).count()
+}
+
+pub fn validate_name(name: &str) -> Result<(), String> {
+    let trimmed = name.trim();
+
+    if trimmed.is_empty() {
+        return Err("Name cannot be empty".to_string());
+    }
+
+    // Untrimmed, since some callers store the name as given
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(format!("Name cannot exceed {} characters", MAX_NAME_LENGTH));
+    }
+
+    if name.chars().any(char::is_control) {
+        return Err("Name cannot contain control characters".to_string());
+    }
+
+    Ok(())
+}
+
+pub fn validate_description(description: &str) -> Result<(), String> {
+    if description.chars().count() > MAX_DESCRIPTION_LENGTH {
+        return Err(format!("Description cannot exceed {} characters", MAX_DESCRIPTION_LENGTH));
+    }
+
+    if description.chars().any(|c| c.is_control() && c != '\n') {
+        return Err("Description cannot contain control characters other than line breaks".to_string());
+    }
+
+    Ok(())
+}
+
+pub fn validate_handle(handle: &str) -> Result<(), String> {
+    let length = handle.chars().count();
+    if !(MIN_HANDLE_LENGTH..=MAX_HANDLE_LENGTH).contains(&length) {
+        return Err(format!(
+            "Handle must be between {} and {} characters",
+            MIN_HANDLE_LENGTH, MAX_HANDLE_LENGTH
+        ));
+    }
+
+    if !handle.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err("Handle may only contain letters, digits and underscores".to_string());
+    }
+
+    if !handle.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return Err("Handle must start with a letter".to_string());
+    }
+
+    if RESERVED_HANDLES.contains(&handle.to_lowercase().as_str()) {
+        return Err("Handle is reserved".to_string());
+    }
+
+    Ok(())
+}
+
+pub fn validate_email(email: &str) -> Result<(), String> {
+    if email.len() > MAX_EMAIL_LENGTH {
+        return Err(format!("Email cannot exceed {} characters", MAX_EMAIL_LENGTH));
+    }
+
+    let (local, domain) = email
+        .split_once('@')
+        .ok_or_else(|| "Email must contain '@'".to_string())?;
+
+    if local.is_empty() || domain.contains('@') {
+        return Err("Invalid email address".to_string());
+    }
+
+    if email.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err("Email cannot contain whitespace".to_string());
+    }
+
+    // Require a dotted domain with non-empty labels, e.g. "example.com"
+    let labels: Vec<&str> = domain.split('.').collect();
+    if labels.len() < 2 || labels.iter().any(|label| label.is_empty()) {
+        return Err("Invalid email domain".to_string());
+    }
+
+    Ok(())
+}
+
+pub fn validate_url(url: &str) -> Result<(), String> {
+    if url.len() > MAX_URL_LENGTH {
+        return Err(format!("URL cannot exceed {} characters", MAX_URL_LENGTH));
+    }
+
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .ok_or_else(|| "URL must start with http:// or https://".to_string())?;
+
+    let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+    if host.is_empty() || !is_url_text(url) {
+        return Err("Invalid URL".to_string());
+    }
+
+    Ok(())
+}
+
+/// Whitespace, control characters, quotes and backslashes have to be
+/// percent-encoded in URLs.
+fn is_url_text(text: &str) -> bool {
+    !text.chars().any(|c| c.is_whitespace() || c.is_control() || c == '"' || c == '\\')
+}
+
+/// Accepts CIDv0 ("Qm..." base58) and CIDv1 ("b..." base32) content identifiers.
+pub fn validate_cid(cid: &str) -> Result<(), String> {
+    let is_v0 = cid.len() == 46
+        && cid.starts_with("Qm")
+        && cid.chars().all(|c| c.is_ascii_alphanumeric() && !"0OIl".contains(c));
+
+    let is_v1 = cid.len() > 8
+        && cid.len() <= MAX_CID_LENGTH
+        && cid.starts_with('b')
+        && cid.chars().all(|c| c.is_ascii_lowercase() || ('2'..='7').contains(&c));
+
+    if is_v0 || is_v1 {
+        Ok(())
+    } else {
+        Err("Invalid IPFS CID".to_string())
+    }
+}
+
+/// Avatars may point to a regular URL, an `ipfs://` URI or a bare CID.
+pub fn validate_avatar_url(avatar_url: &str) -> Result<(), String> {
+    if avatar_url.starts_with("ipfs://") {
+        return validate_ipfs_uri(avatar_url);
+    }
+
+    if avatar_url.starts_with("http://") || avatar_url.starts_with("https://") {
+        return validate_url(avatar_url);
+    }
+
+    validate_cid(avatar_url)
+        .map_err(|_| "Avatar must be an http(s) URL, an ipfs:// URI or an IPFS CID".to_string())
+}
+
+/// NFT metadata lives at an `ipfs://` URI or a regular URL.
+pub fn validate_metadata_uri(metadata_uri: &str) -> Result<(), String> {
+    if metadata_uri.starts_with("ipfs://") {
+        return validate_ipfs_uri(metadata_uri);
+    }
+
+    validate_url(metadata_uri)
+        .map_err(|_| "Metadata URI must be an ipfs:// URI or an http(s) URL".to_string())
+}
+
+/// "ipfs://" followed by a CID and an optional path.
+fn validate_ipfs_uri(uri: &str) -> Result<(), String> {
+    let path = uri.strip_prefix("ipfs://").unwrap_or_default();
+
+    if uri.len() > MAX_URL_LENGTH || !is_url_text(path) {
+        return Err("Invalid IPFS URI".to_string());
+    }
+
+    let cid = path.split('/').next().unwrap_or("");
+    validate_cid(cid)
+}
+
+pub fn validate_nft_title(title: &str) -> Result<(), String> {
+    let trimmed = title.trim();
+
+    if trimmed.is_empty() {
+        return Err("Title cannot be empty".to_string());
+    }
+
+    if title.chars().count() > MAX_TITLE_LENGTH {
+        return Err(format!("Title cannot exceed {} characters", MAX_TITLE_LENGTH));
+    }
+
+    if title.chars().any(char::is_control) {
+        return Err("Title cannot contain control characters".to_string());
+    }
+
+    Ok(())
+}
+
+pub fn validate_token_id(token_id: &str) -> Result<(), String> {
+    if token_id.is_empty() || token_id.len() > MAX_TOKEN_ID_LENGTH || !token_id.bytes().all(|b| b.is_ascii_digit()) {
+        return Err("Token id must be a decimal number".to_string());
+    }
+
+    Ok(())
+}
+
+/// An EVM address: "0x" followed by 40 hex digits.
+pub fn validate_contract_address(address: &str) -> Result<(), String> {
+    let valid = address
+        .strip_prefix("0x")
+        .is_some_and(|hex| hex.len() == 40 && hex.bytes().all(|b| b.is_ascii_hexdigit()));
+
+    if !valid {
+        return Err("Contract address must be 0x followed by 40 hex digits".to_string());
+    }
+
+    Ok(())
+}
+
+pub fn validate_bio(bio: &str) -> Result<(), String> {
+    if bio.chars().count() > MAX_BIO_LENGTH {
+        return Err(format!("Bio cannot exceed {} characters", MAX_BIO_LENGTH));
+    }
+
+    // Non-Latin scripts and emoji take several bytes per character
+    if stored_len(bio) > MAX_BIO_BYTES {
+        return Err(format!("Bio cannot exceed {} bytes", MAX_BIO_BYTES));
+    }
+
+    if bio.chars().any(|c| c.is_control() && c != '\n') {
+        return Err("Bio cannot contain control characters other than line breaks".to_string());
+    }
+
+    Ok(())
+}
+
+pub fn validate_location(location: &str) -> Result<(), String> {
+    if location.chars().count() > MAX_LOCATION_LENGTH {
+        return Err(format!("Location cannot exceed {} characters", MAX_LOCATION_LENGTH));
+    }
+
+    if location.chars().any(char::is_control) {
+        return Err("Location cannot contain control characters".to_string());
+    }
+
+    Ok(())
+}
+
+pub fn validate_links(links: &[String]) -> Result<(), String> {
+    if links.len() > MAX_LINKS {
+        return Err(format!("Cannot have more than {} links", MAX_LINKS));
+    }
+
+    for link in links {
+        if link.len() > MAX_LINK_LENGTH {
+            return Err(format!("Links cannot exceed {} characters", MAX_LINK_LENGTH));
+        }
+
+        validate_url(link).map_err(|e| format!("Invalid link '{}': {}", link, e))?;
+    }
+
+    Ok(())
+}
+
+/// Checks a list of genres, moods or tags. `label` names the list in errors.
+pub fn validate_tags(label: &str, tags: &[String]) -> Result<(), String> {
+    if tags.len() > MAX_TAGS {
+        return Err(format!("Cannot have more than {} {}", MAX_TAGS, label));
+    }
+
+    for tag in tags {
+        let trimmed = tag.trim();
+
+        if trimmed.is_empty() || trimmed.chars().count() > MAX_TAG_LENGTH {
+            return Err(format!("Each of the {} must be 1 to {} characters", label, MAX_TAG_LENGTH));
+        }
+
+        if !trimmed.chars().all(|c| c.is_alphanumeric() || c == ' ' || c == '-') {
+            return Err(format!("'{}' may only contain letters, digits, spaces and hyphens", trimmed));
+        }
+    }
+
+    Ok(())
+}
+
+/// A collaborator's role on a project, e.g. "Producer".
+pub fn validate_role(role: &str) -> Result<(), String> {
+    let trimmed = role.trim();
+
+    if trimmed.is_empty() {
+        return Err("Role cannot be empty".to_string());
+    }
+
+    if trimmed.chars().count() > MAX_ROLE_LENGTH {
+        return Err(format!("Role cannot exceed {} characters", MAX_ROLE_LENGTH));
+    }
+
+    if trimmed.chars().any(char::is_control) {
+        return Err("Role cannot contain control characters".to_string());
+    }
+
+    Ok(())
+}
+
+pub fn validate_suspension_reason(reason: &str) -> Result<(), String> {
+    let trimmed = reason.trim();
+
+    if trimmed.is_empty() {
+        return Err("A reason is required".to_string());
+    }
+
+    if trimmed.chars().count() > MAX_SUSPENSION_REASON_LENGTH {
+        return Err(format!("Reason cannot exceed {} characters", MAX_SUSPENSION_REASON_LENGTH));
+    }
+
+    if trimmed.chars().any(char::is_control) {
+        return Err("Reason cannot contain control characters".to_string());
+    }
+
+    Ok(())
+}
+
+pub fn validate_snapshot_label(label: &str) -> Result<(), String> {
+    let trimmed = label.trim();
+
+    if trimmed.is_empty() {
+        return Err("Snapshot label cannot be empty".to_string());
+    }
+
+    if trimmed.chars().count() > MAX_SNAPSHOT_LABEL_LENGTH {
+        return Err(format!("Snapshot label cannot exceed {} characters", MAX_SNAPSHOT_LABEL_LENGTH));
+    }
+
+    Ok(())
+}
+
+pub fn validate_version_notes(notes: &str) -> Result<(), String> {
+    if notes.chars().count() > MAX_VERSION_NOTES_LENGTH {
+        return Err(format!("Version notes cannot exceed {} characters", MAX_VERSION_NOTES_LENGTH));
+    }
+
+    if stored_len(notes) > MAX_VERSION_NOTES_BYTES {
+        return Err(format!("Version notes cannot exceed {} bytes", MAX_VERSION_NOTES_BYTES));
+    }
+
+    if notes.chars().any(|c| c.is_control() && c != '\n') {
+        return Err("Version notes cannot contain control characters other than line breaks".to_string());
+    }
+
+    Ok(())
+}
+
+pub fn validate_audio_format(sample_rate: u32, channels: u8) -> Result<(), String> {
+    if !SAMPLE_RATES.contains(&sample_rate) {
+        return Err(format!("Unsupported sample rate: {} Hz", sample_rate));
+    }
+
+    if channels == 0 || channels > MAX_CHANNELS {
+        return Err(format!("Channel count must be between 1 and {}", MAX_CHANNELS));
+    }
+
+    Ok(())
+}
+
+pub fn validate_track_metadata(metadata: &TrackMetadata) -> Result<(), String> {
+    if let Some(bpm) = metadata.bpm {
+        if !(MIN_BPM..=MAX_BPM).contains(&bpm) {
+            return Err(format!("BPM must be between {} and {}", MIN_BPM, MAX_BPM));
+        }
+    }
+
+    if let Some(key) = &metadata.key {
+        validate_musical_key(key)?;
+    }
+
+    if let Some(time_signature) = &metadata.time_signature {
+        validate_time_signature(time_signature)?;
+    }
+
+    if let Some(genre) = &metadata.genre {
+        validate_tags("genre", std::slice::from_ref(genre))?;
+    }
+
+    if let Some(isrc) = &metadata.isrc {
+        validate_isrc(isrc)?;
+    }
+
+    if let Some(language) = &metadata.language {
+        if language.len() != 2 || !language.chars().all(|c| c.is_ascii_lowercase()) {
+            return Err("Language must be a two-letter ISO 639-1 code such as 'en'".to_string());
+        }
+    }
+
+    Ok(())
+}
+
+pub fn validate_track_details(lyrics: Option<&str>, credits: &[TrackCredit]) -> Result<(), String> {
+    if let Some(lyrics) = lyrics {
+        if lyrics.chars().count() > MAX_LYRICS_LENGTH {
+            return Err(format!("Lyrics cannot exceed {} characters", MAX_LYRICS_LENGTH));
+        }
+
+        if lyrics.chars().any(|c| c.is_control() && c != '\n') {
+            return Err("Lyrics cannot contain control characters other than line breaks".to_string());
+        }
+    }
+
+    if credits.len() > MAX_CREDITS {
+        return Err(format!("Cannot have more than {} credits", MAX_CREDITS));
+    }
+
+    for credit in credits {
+        validate_name(&credit.name).map_err(|e| format!("Invalid credit name: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// A root note with an optional accidental and mode: "C", "F#m", "Bb minor",
+/// "Ebmaj".
+pub fn validate_musical_key(key: &str) -> Result<(), String> {
+    let mut chars = key.chars();
+
+    let valid = match chars.next() {
+        Some('A'..='G') => {
+            let rest = chars.as_str();
+            let mode = rest.strip_prefix(['#', 'b']).unwrap_or(rest);
+            ["", "m", "maj", "min", " major", " minor"].contains(&mode)
+        }
+        _ => false,
+    };
+
+    if !valid {
+        return Err(format!("Invalid key '{}'; use notation like C, F#m or Bb minor", key));
+    }
+
+    Ok(())
+}
+
+/// "beats/unit", where the unit is a power of two: "4/4", "7/8", "12/8".
+pub fn validate_time_signature(time_signature: &str) -> Result<(), String> {
+    let parsed = time_signature
+        .split_once('/')
+        .and_then(|(beats, unit)| Some((beats.parse::<u8>().ok()?, unit.parse::<u8>().ok()?)));
+
+    match parsed {
+        Some((1..=32, unit)) if unit.is_power_of_two() && unit <= 64 => Ok(()),
+        _ => Err(format!("Invalid time signature '{}'; use notation like 4/4 or 6/8", time_signature)),
+    }
+}
+
+/// Country code, registrant code, year and designation code, e.g.
+/// "USRC17607839". Expects hyphens to be stripped already.
+pub fn validate_isrc(isrc: &str) -> Result<(), String> {
+    let bytes = isrc.as_bytes();
+
+    let valid = bytes.len() == 12
+        && bytes[..2].iter().all(u8::is_ascii_uppercase)
+        && bytes[2..5].iter().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+        && bytes[5..].iter().all(u8::is_ascii_digit);
+
+    if !valid {
+        return Err(format!("Invalid ISRC '{}'; expected a code like USRC17607839", isrc));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CID_V0: &str = "QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG";
+    const CID_V1: &str = "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi";
+
+    #[test]
+    fn names_are_trimmed_and_bounded() {
+        assert!(validate_name("  Aurora  ").is_ok());
+        assert!(validate_name(&"a".repeat(MAX_NAME_LENGTH)).is_ok());
+
+        assert!(validate_name("   ").is_err());
+        assert!(validate_name(&"a".repeat(MAX_NAME_LENGTH + 1)).is_err());
+        assert!(validate_name("tab\there").is_err());
+    }
+
+    #[test]
+    fn descriptions_allow_line_breaks_only() {
+        assert!(validate_description("").is_ok());
+        assert!(validate_description("line one\nline two").is_ok());
+
+        assert!(validate_description("bell\u{7}").is_err());
+        assert!(validate_description(&"a".repeat(MAX_DESCRIPTION_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn handles_follow_the_handle_rules() {
+        assert!(validate_handle("dj_aurora").is_ok());
+
+        for handle in ["ab", "9lives", "_aurora", "dj-aurora", "dj aurora", "Admin"] {
+            assert!(validate_handle(handle).is_err(), "{:?} should be rejected", handle);
+        }
+        assert!(validate_handle(&"a".repeat(MAX_HANDLE_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn emails_need_a_local_part_and_a_dotted_domain() {
+        assert!(validate_email("artist@example.com").is_ok());
+        assert!(validate_email("first.last+tag@mail.example.co").is_ok());
+
+        for email in [
+            "artist.example.com",
+            "@example.com",
+            "artist@@example.com",
+            "artist@example",
+            "artist@example.",
+            "artist@.com",
+            "art ist@example.com",
+        ] {
+            assert!(validate_email(email).is_err(), "{:?} should be rejected", email);
+        }
+
+        let long = format!("{}@example.com", "a".repeat(MAX_EMAIL_LENGTH));
+        assert!(validate_email(&long).is_err());
+    }
+
+    #[test]
+    fn urls_need_an_http_scheme_and_a_host() {
+        assert!(validate_url("https://example.com").is_ok());
+        assert!(validate_url("http://example.com/path?q=1").is_ok());
+
+        for url in [
+            "ftp://example.com",
+            "example.com",
+            "https://",
+            "https:///path",
+            "https://exa mple.com",
+            "https://example.com/\"quoted\"",
+            "https://example.com/\u{0}",
+        ] {
+            assert!(validate_url(url).is_err(), "{:?} should be rejected", url);
+        }
+    }
+
+    #[test]
+    fn cids_accept_v0_and_v1() {
+        assert!(validate_cid(CID_V0).is_ok());
+        assert!(validate_cid(CID_V1).is_ok());
+
+        assert!(validate_cid("").is_err());
+        assert!(validate_cid("not-a-cid").is_err());
+        assert!(validate_cid(&CID_V0[..45]).is_err());
+        assert!(validate_cid(&CID_V1.to_uppercase()).is_err());
+        assert!(validate_cid(&format!("b{}", "a".repeat(MAX_CID_LENGTH))).is_err());
+    }
+
+    #[test]
+    fn avatars_accept_urls_ipfs_uris_and_cids() {
+        assert!(validate_avatar_url("https://example.com/avatar.png").is_ok());
+        assert!(validate_avatar_url(&format!("ipfs://{}/avatar.png", CID_V1)).is_ok());
+        assert!(validate_avatar_url(CID_V0).is_ok());
+
+        assert!(validate_avatar_url("ipfs://not-a-cid").is_err());
+        assert!(validate_avatar_url(&format!("ipfs://{}/{}", CID_V1, "a".repeat(MAX_URL_LENGTH))).is_err());
+        assert!(validate_avatar_url("avatar.png").is_err());
+    }
+
+    #[test]
+    fn profile_text_is_bounded() {
+        assert!(validate_bio(&"a".repeat(MAX_BIO_LENGTH)).is_ok());
+        assert!(validate_bio(&"a".repeat(MAX_BIO_LENGTH + 1)).is_err());
+        assert!(validate_bio("line one\nline two").is_ok());
+        assert!(validate_bio("bell\u{7}").is_err());
+
+        // Within the character limit, but not the byte limit
+        assert!(validate_bio(&"\"".repeat(MAX_BIO_LENGTH)).is_ok());
+        assert!(validate_bio(&"🎸".repeat(MAX_BIO_LENGTH)).is_err());
+
+        assert!(validate_location(&"a".repeat(MAX_LOCATION_LENGTH)).is_ok());
+        assert!(validate_location(&"a".repeat(MAX_LOCATION_LENGTH + 1)).is_err());
+        assert!(validate_location("Berlin\n").is_err());
+    }
+
+    #[test]
+    fn links_are_counted_and_checked() {
+        let link = "https://example.com".to_string();
+
+        assert!(validate_links(&vec![link.clone(); MAX_LINKS]).is_ok());
+        assert!(validate_links(&vec![link; MAX_LINKS + 1]).is_err());
+        assert!(validate_links(&["example.com".to_string()]).is_err());
+        assert!(validate_links(&[format!("https://example.com/{}", "a".repeat(MAX_LINK_LENGTH))]).is_err());
+    }
+
+    #[test]
+    fn tags_are_counted_and_checked() {
+        let tags = |values: &[&str]| values.iter().map(|value| value.to_string()).collect::<Vec<_>>();
+
+        assert!(validate_tags("tags", &tags(&["lo-fi", "hip hop", "ambient"])).is_ok());
+
+        assert!(validate_tags("tags", &tags(&[""])).is_err());
+        assert!(validate_tags("tags", &tags(&["rock & roll"])).is_err());
+        assert!(validate_tags("tags", &["a".repeat(MAX_TAG_LENGTH + 1)]).is_err());
+        assert!(validate_tags("tags", &vec!["rock".to_string(); MAX_TAGS + 1]).is_err());
+    }
+
+    #[test]
+    fn isrcs_must_be_canonical() {
+        assert!(validate_isrc("USRC17607839").is_ok());
+
+        for isrc in ["US-RC1-76-07839", "usrc17607839", "USRC1760783", "USRC1760783X"] {
+            assert!(validate_isrc(isrc).is_err(), "{:?} should be rejected", isrc);
+        }
+    }
+
+    #[test]
+    fn isrc_errors_show_the_canonical_form() {
+        let error = validate_isrc("bad").unwrap_err();
+        assert!(error.contains("USRC17607839"), "{}", error);
+    }
+}